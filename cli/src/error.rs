@@ -0,0 +1,113 @@
+//! Error classes and exit codes
+
+use std::fmt;
+
+use nostr_android_signer::prelude::Error as AndroidSignerError;
+use serde_json::{Value, json};
+use tonic::Code;
+
+/// CLI error
+#[derive(Debug)]
+pub enum Error {
+    /// Android signer error
+    AndroidSigner(AndroidSignerError),
+    /// Invalid command-line arguments or input
+    InvalidInput(String),
+    /// Internal error (i.e., serialization of the output)
+    Internal(String),
+}
+
+impl std::error::Error for Error {}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::AndroidSigner(e) => e.fmt(f),
+            Self::InvalidInput(e) => write!(f, "Invalid input: {e}"),
+            Self::Internal(e) => write!(f, "Internal error: {e}"),
+        }
+    }
+}
+
+impl From<AndroidSignerError> for Error {
+    fn from(e: AndroidSignerError) -> Self {
+        Self::AndroidSigner(e)
+    }
+}
+
+/// Error class, with its exit code
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ErrorClass {
+    pub name: &'static str,
+    pub exit_code: u8,
+}
+
+impl ErrorClass {
+    const INTERNAL: Self = Self::new("internal", 1);
+    const UNAVAILABLE: Self = Self::new("unavailable", 3);
+    const UNAUTHENTICATED: Self = Self::new("unauthenticated", 4);
+    const REJECTED: Self = Self::new("rejected", 5);
+    const POLICY_DENIED: Self = Self::new("policy_denied", 6);
+    const SIGNER_NOT_INSTALLED: Self = Self::new("signer_not_installed", 7);
+    const INVALID_ARGUMENT: Self = Self::new("invalid_argument", 8);
+    const UNSUPPORTED: Self = Self::new("unsupported", 9);
+    const INVALID_SIGNER_RESPONSE: Self = Self::new("invalid_signer_response", 10);
+    const TIMEOUT: Self = Self::new("timeout", 11);
+    const CANCELLED: Self = Self::new("cancelled", 12);
+
+    const fn new(name: &'static str, exit_code: u8) -> Self {
+        Self { name, exit_code }
+    }
+}
+
+impl From<&Error> for ErrorClass {
+    fn from(e: &Error) -> Self {
+        match e {
+            Error::AndroidSigner(e) => Self::from(e),
+            Error::InvalidInput(..) => Self::INVALID_ARGUMENT,
+            Error::Internal(..) => Self::INTERNAL,
+        }
+    }
+}
+
+impl From<&AndroidSignerError> for ErrorClass {
+    fn from(e: &AndroidSignerError) -> Self {
+        match e {
+            AndroidSignerError::IO(..) | AndroidSignerError::Transport(..) => Self::UNAVAILABLE,
+            AndroidSignerError::Status(status) => match status.code() {
+                Code::Unavailable => Self::UNAVAILABLE,
+                _ => Self::INTERNAL,
+            },
+            // Parsing errors of the replies
+            AndroidSignerError::Keys(..)
+            | AndroidSignerError::Event(..)
+            | AndroidSignerError::SignedEventMismatch(..)
+            | AndroidSignerError::InvalidSignerResponse(..) => Self::INVALID_SIGNER_RESPONSE,
+            AndroidSignerError::Unauthenticated => Self::UNAUTHENTICATED,
+            AndroidSignerError::Rejected => Self::REJECTED,
+            AndroidSignerError::PolicyDenied(..) => Self::POLICY_DENIED,
+            AndroidSignerError::SignerNotInstalled => Self::SIGNER_NOT_INSTALLED,
+            AndroidSignerError::InvalidArgument(..) => Self::INVALID_ARGUMENT,
+            AndroidSignerError::Unsupported(..) => Self::UNSUPPORTED,
+            AndroidSignerError::Timeout => Self::TIMEOUT,
+            AndroidSignerError::Cancelled => Self::CANCELLED,
+        }
+    }
+}
+
+/// JSON output of an error
+pub fn to_json(e: &Error) -> Value {
+    let class: ErrorClass = ErrorClass::from(e);
+    let policy_rule: Option<&str> = match e {
+        Error::AndroidSigner(AndroidSignerError::PolicyDenied(rule)) => Some(rule),
+        _ => None,
+    };
+
+    json!({
+        "error": {
+            "class": class.name,
+            "message": e.to_string(),
+            "policy_rule": policy_rule,
+        }
+    })
+}
@@ -0,0 +1,270 @@
+//! Command-line client for the Android signer proxy (NIP-55)
+//!
+//! Talks to a running proxy (i.e., from `adb shell`), printing the results as JSON.
+
+#![forbid(unsafe_code)]
+#![warn(clippy::large_futures)]
+
+use std::io::{self, Read};
+use std::net::SocketAddr;
+use std::path::PathBuf;
+use std::process::ExitCode;
+use std::time::{Duration, Instant};
+
+use clap::{Args, Parser, Subcommand};
+use nostr_android_signer::prelude::{Error as AndroidSignerError, *};
+use serde_json::{Map, Value, json};
+
+mod error;
+#[cfg(test)]
+mod tests;
+
+use self::error::{Error, ErrorClass};
+
+/// Command-line client for the Android signer proxy (NIP-55)
+///
+/// Results are printed as JSON to stdout, errors too (`{"error": {"class": ..., "message": ...}}`).
+/// The exit code depends on the error class.
+#[derive(Debug, Parser)]
+#[command(name = "nostr-android-signer", version, about)]
+struct Cli {
+    #[command(flatten)]
+    proxy: ProxyArgs,
+    /// Auth token of the proxy
+    #[arg(long, env = "NIP55_AUTH_TOKEN", hide_env_values = true)]
+    auth_token: Option<String>,
+    /// Timeout of the non-interactive requests, in seconds
+    #[arg(long, default_value_t = 10)]
+    timeout: u64,
+    /// Timeout of the requests that need the user approval, in seconds
+    #[arg(long, default_value_t = 120)]
+    interactive_timeout: u64,
+    #[command(subcommand)]
+    command: Command,
+}
+
+/// Address of the proxy
+#[derive(Debug, Args)]
+#[group(required = true, multiple = false)]
+struct ProxyArgs {
+    /// Unique name of the proxy (abstract socket `nip55_proxy_<name>`)
+    #[arg(long)]
+    name: Option<String>,
+    /// Filesystem UNIX socket of the proxy
+    #[arg(long)]
+    path: Option<PathBuf>,
+    /// Loopback TCP address of the proxy (i.e., `127.0.0.1:<port>` after `adb forward`)
+    #[arg(long)]
+    tcp: Option<SocketAddr>,
+}
+
+#[derive(Debug, Subcommand)]
+enum Command {
+    /// Check if an external signer app is installed
+    Installed,
+    /// Get the public key of the current user
+    Pubkey {
+        /// Always ask the signer, instead of using the public key cached by the proxy
+        #[arg(long)]
+        login: bool,
+    },
+    /// Sign an event
+    ///
+    /// Without `--kind`, the unsigned event JSON is read from stdin.
+    /// Missing `pubkey`, `created_at`, `tags` and `content` are filled in.
+    Sign {
+        /// Event kind
+        #[arg(long)]
+        kind: Option<u16>,
+        /// Event content
+        #[arg(long, requires = "kind", default_value = "")]
+        content: String,
+        /// Event tag, as JSON array (i.e., `["t","nostr"]`). Can be repeated.
+        #[arg(long = "tag", requires = "kind")]
+        tags: Vec<String>,
+    },
+    /// Encrypt with NIP-04
+    Nip04Encrypt(CryptoArgs),
+    /// Decrypt with NIP-04
+    Nip04Decrypt(CryptoArgs),
+    /// Encrypt with NIP-44
+    Nip44Encrypt(CryptoArgs),
+    /// Decrypt with NIP-44
+    Nip44Decrypt(CryptoArgs),
+    /// Check that the proxy is reachable, measuring the round-trip time
+    Ping,
+}
+
+#[derive(Debug, Args)]
+struct CryptoArgs {
+    /// Public key of the other user (hex or npub)
+    #[arg(long)]
+    pubkey: String,
+    /// Text to encrypt or decrypt (default: read from stdin)
+    #[arg(long)]
+    text: Option<String>,
+}
+
+#[tokio::main(flavor = "current_thread")]
+async fn main() -> ExitCode {
+    let cli: Cli = Cli::parse();
+
+    match run(cli).await {
+        Ok(output) => {
+            println!("{output}");
+            ExitCode::SUCCESS
+        }
+        Err(e) => {
+            println!("{}", error::to_json(&e));
+            ExitCode::from(ErrorClass::from(&e).exit_code)
+        }
+    }
+}
+
+async fn run(cli: Cli) -> Result<Value, Error> {
+    let signer: AndroidSigner = signer(&cli)?;
+
+    match cli.command {
+        Command::Installed => {
+            let installed: bool = signer.is_external_signer_installed().await?;
+            Ok(json!({ "installed": installed }))
+        }
+        Command::Pubkey { login } => {
+            let public_key: PublicKey = if login {
+                signer.login().await?
+            } else {
+                signer.public_key().await?
+            };
+            Ok(json!({
+                "public_key": public_key.to_hex(),
+                "npub": public_key.to_bech32().map_err(|e| Error::Internal(e.to_string()))?,
+            }))
+        }
+        Command::Sign {
+            kind,
+            content,
+            tags,
+        } => {
+            let public_key: PublicKey = signer.public_key().await?;
+            let unsigned: UnsignedEvent = match kind {
+                Some(kind) => {
+                    let tags: Vec<Tag> = tags
+                        .iter()
+                        .map(|tag| parse_tag(tag))
+                        .collect::<Result<_, _>>()?;
+                    EventBuilder::new(Kind::from(kind), content)
+                        .tags(tags)
+                        .build(public_key)
+                }
+                None => unsigned_event_from_json(&read_stdin()?, &public_key)?,
+            };
+            let event: Event = signer.sign_event_as(unsigned).await?;
+            serde_json::to_value(&event).map_err(|e| Error::Internal(e.to_string()))
+        }
+        Command::Nip04Encrypt(args) => {
+            let (current_user, other, text) = crypto_args(&signer, args).await?;
+            let ciphertext: String = signer
+                .nip04_encrypt_as(&current_user, &other, &text)
+                .await?;
+            Ok(json!({ "ciphertext": ciphertext }))
+        }
+        Command::Nip04Decrypt(args) => {
+            let (current_user, other, text) = crypto_args(&signer, args).await?;
+            let plaintext: String = signer
+                .nip04_decrypt_as(&current_user, &other, &text)
+                .await?;
+            Ok(json!({ "plaintext": plaintext }))
+        }
+        Command::Nip44Encrypt(args) => {
+            let (current_user, other, text) = crypto_args(&signer, args).await?;
+            let ciphertext: String = signer
+                .nip44_encrypt_as(&current_user, &other, &text)
+                .await?;
+            Ok(json!({ "ciphertext": ciphertext }))
+        }
+        Command::Nip44Decrypt(args) => {
+            let (current_user, other, text) = crypto_args(&signer, args).await?;
+            let plaintext: String = signer
+                .nip44_decrypt_as(&current_user, &other, &text)
+                .await?;
+            Ok(json!({ "plaintext": plaintext }))
+        }
+        Command::Ping => {
+            let started_at: Instant = Instant::now();
+            let installed: bool = signer.is_external_signer_installed().await?;
+            Ok(json!({
+                "ok": true,
+                "installed": installed,
+                "latency_ms": started_at.elapsed().as_millis() as u64,
+            }))
+        }
+    }
+}
+
+fn signer(cli: &Cli) -> Result<AndroidSigner, Error> {
+    // Fail fast: a CLI must not wait for the proxy to come up
+    let mut opts: AndroidSignerOptions = AndroidSignerOptions::new()
+        .reconnect(false)
+        .timeout(Duration::from_secs(cli.timeout))
+        .interactive_timeout(Duration::from_secs(cli.interactive_timeout));
+
+    if let Some(token) = &cli.auth_token {
+        opts = opts.auth_token(token);
+    }
+
+    match (&cli.proxy.name, &cli.proxy.path, cli.proxy.tcp) {
+        (Some(name), ..) => AndroidSigner::new(name, opts),
+        (_, Some(path), _) => AndroidSigner::with_path(path, opts),
+        (.., Some(addr)) => AndroidSigner::tcp(addr, opts),
+        (None, None, None) => Err(AndroidSignerError::InvalidArgument(String::from(
+            "missing proxy address",
+        ))),
+    }
+    .map_err(Error::from)
+}
+
+async fn crypto_args(
+    signer: &AndroidSigner,
+    args: CryptoArgs,
+) -> Result<(PublicKey, PublicKey, String), Error> {
+    let other: PublicKey =
+        PublicKey::parse(&args.pubkey).map_err(|e| Error::InvalidInput(format!("pubkey: {e}")))?;
+    let text: String = match args.text {
+        Some(text) => text,
+        None => read_stdin()?,
+    };
+    let current_user: PublicKey = signer.public_key().await?;
+    Ok((current_user, other, text))
+}
+
+fn read_stdin() -> Result<String, Error> {
+    let mut input: String = String::new();
+    io::stdin()
+        .read_to_string(&mut input)
+        .map_err(|e| Error::InvalidInput(format!("stdin: {e}")))?;
+    Ok(input)
+}
+
+fn parse_tag(tag: &str) -> Result<Tag, Error> {
+    let tag: Vec<String> =
+        serde_json::from_str(tag).map_err(|e| Error::InvalidInput(format!("tag: {e}")))?;
+    Tag::parse(tag).map_err(|e| Error::InvalidInput(format!("tag: {e}")))
+}
+
+/// Parse an unsigned event, filling in the missing fields
+fn unsigned_event_from_json(json: &str, public_key: &PublicKey) -> Result<UnsignedEvent, Error> {
+    let mut event: Map<String, Value> = serde_json::from_str(json)
+        .map_err(|e| Error::InvalidInput(format!("unsigned event: {e}")))?;
+
+    event
+        .entry("pubkey")
+        .or_insert_with(|| Value::from(public_key.to_hex()));
+    event
+        .entry("created_at")
+        .or_insert_with(|| Value::from(Timestamp::now().as_secs()));
+    event.entry("tags").or_insert_with(|| json!([]));
+    event.entry("content").or_insert_with(|| json!(""));
+
+    UnsignedEvent::from_json(Value::Object(event).to_string())
+        .map_err(|e| Error::InvalidInput(format!("unsigned event: {e}")))
+}
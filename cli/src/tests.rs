@@ -0,0 +1,218 @@
+//! Exit code tests
+//!
+//! The commands run against the mock proxy, scripted to fail.
+
+use std::io;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::time::Duration;
+
+use clap::Parser;
+use nostr_android_signer::prelude::{Error as AndroidSignerError, *};
+use nostr_android_signer_mock::prelude::{Behavior, Code, MockProxy, Rpc};
+use serde_json::Value;
+use tonic::Status;
+
+use crate::error::{Error, ErrorClass};
+use crate::{Cli, run};
+
+static SOCKET_COUNTER: AtomicUsize = AtomicUsize::new(0);
+
+/// Random unique name, so tests can run in parallel
+fn unique_name() -> String {
+    let n: usize = SOCKET_COUNTER.fetch_add(1, Ordering::SeqCst);
+    let nonce: String = Keys::generate().public_key().to_hex();
+    format!("cli_test_{}_{n}_{}", std::process::id(), &nonce[..8])
+}
+
+/// Exit codes documented in the README
+fn documented_exit_codes() -> Vec<(u8, String)> {
+    include_str!("../README.md")
+        .lines()
+        .filter_map(|line| {
+            let mut columns = line.split('|').map(str::trim).skip(1);
+            let code: u8 = columns.next()?.parse().ok()?;
+            let class: &str = columns.next()?.strip_prefix('`')?.strip_suffix('`')?;
+            Some((code, class.to_string()))
+        })
+        .collect()
+}
+
+fn class(e: Error) -> (u8, String) {
+    let class: ErrorClass = ErrorClass::from(&e);
+    (class.exit_code, class.name.to_string())
+}
+
+#[test]
+fn test_exit_code_table() {
+    let documented: Vec<(u8, String)> = documented_exit_codes();
+
+    let cases: Vec<(Error, &str)> = vec![
+        (Error::Internal(String::from("json")), "internal"),
+        (
+            AndroidSignerError::IO(io::Error::other("io")).into(),
+            "unavailable",
+        ),
+        (
+            AndroidSignerError::Status(Status::unavailable("down")).into(),
+            "unavailable",
+        ),
+        (
+            AndroidSignerError::Status(Status::internal("bug")).into(),
+            "internal",
+        ),
+        (
+            AndroidSignerError::Unauthenticated.into(),
+            "unauthenticated",
+        ),
+        (AndroidSignerError::Rejected.into(), "rejected"),
+        (
+            AndroidSignerError::PolicyDenied(String::from("rule")).into(),
+            "policy_denied",
+        ),
+        (
+            AndroidSignerError::SignerNotInstalled.into(),
+            "signer_not_installed",
+        ),
+        (
+            AndroidSignerError::InvalidArgument(String::from("arg")).into(),
+            "invalid_argument",
+        ),
+        (Error::InvalidInput(String::from("tag")), "invalid_argument"),
+        (
+            AndroidSignerError::Unsupported(String::from("op")).into(),
+            "unsupported",
+        ),
+        (
+            AndroidSignerError::Keys(PublicKey::parse("invalid").unwrap_err()).into(),
+            "invalid_signer_response",
+        ),
+        (
+            AndroidSignerError::Event(Event::from_json("invalid").unwrap_err()).into(),
+            "invalid_signer_response",
+        ),
+        (
+            AndroidSignerError::SignedEventMismatch(String::from("id")).into(),
+            "invalid_signer_response",
+        ),
+        (
+            AndroidSignerError::InvalidSignerResponse(String::from("reply")).into(),
+            "invalid_signer_response",
+        ),
+        (AndroidSignerError::Timeout.into(), "timeout"),
+        (AndroidSignerError::Cancelled.into(), "cancelled"),
+    ];
+
+    for (e, expected) in cases {
+        let message: String = e.to_string();
+        let (code, name) = class(e);
+        assert_eq!(name, expected, "{message}");
+        assert!(
+            documented.contains(&(code, name.clone())),
+            "{name} ({code}) is not documented"
+        );
+    }
+
+    // Exit code 2 is for the invalid command lines (clap)
+    assert!(documented.iter().all(|(code, _)| *code != 2));
+}
+
+/// Run a command against a proxy
+async fn exec(name: &str, args: &[&str]) -> Result<Value, (u8, String)> {
+    let cli: Cli = Cli::try_parse_from(
+        [
+            "nostr-android-signer",
+            "--name",
+            name,
+            "--interactive-timeout",
+            "1",
+        ]
+        .iter()
+        .chain(args),
+    )
+    .unwrap();
+    run(cli).await.map_err(class)
+}
+
+fn exit_code(res: Result<Value, (u8, String)>) -> u8 {
+    res.unwrap_err().0
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn test_exit_codes() {
+    let name: String = unique_name();
+    let keys: Keys = Keys::generate();
+    let mock = MockProxy::new(&name, keys.clone()).unwrap();
+    let handle = mock.spawn().unwrap();
+
+    let sign: [&str; 3] = ["sign", "--kind", "1"];
+    let other: String = Keys::generate().public_key().to_hex();
+
+    let res = exec(&name, &["pubkey"]).await.unwrap();
+    assert_eq!(res["public_key"], keys.public_key().to_hex());
+
+    // No proxy
+    assert_eq!(exit_code(exec(&unique_name(), &["installed"]).await), 3);
+
+    // The current user is requested with the detailed errors too
+    mock.set_behavior(Rpc::GetPublicKey, Behavior::reject())
+        .await;
+    assert_eq!(exit_code(exec(&name, &["pubkey"]).await), 5);
+    assert_eq!(exit_code(exec(&name, &["pubkey", "--login"]).await), 5);
+    assert_eq!(exit_code(exec(&name, &sign).await), 5);
+    let res = exec(&name, &["nip44-encrypt", "--pubkey", &other, "--text", "a"]).await;
+    assert_eq!(exit_code(res), 5);
+    mock.set_behavior(
+        Rpc::GetPublicKey,
+        Behavior::approve().delay(Duration::from_secs(3)),
+    )
+    .await;
+    assert_eq!(exit_code(exec(&name, &["pubkey"]).await), 11);
+    mock.reset().await;
+
+    let cases: [(Behavior, u8); 5] = [
+        (Behavior::reject(), 5),
+        (Behavior::fail(Code::FailedPrecondition, "none"), 7),
+        (Behavior::fail(Code::Unimplemented, "unsupported"), 9),
+        (Behavior::tampered(), 10),
+        (Behavior::approve().delay(Duration::from_secs(3)), 11),
+    ];
+    for (behavior, expected) in cases {
+        mock.set_behavior(Rpc::SignEvent, behavior.clone()).await;
+        assert_eq!(
+            exit_code(exec(&name, &sign).await),
+            expected,
+            "{behavior:?}"
+        );
+    }
+    mock.reset().await;
+
+    let res = exec(&name, &sign).await.unwrap();
+    assert_eq!(res["pubkey"], keys.public_key().to_hex());
+
+    // Invalid input, never sent to the proxy
+    let res = exec(
+        &name,
+        &["nip44-encrypt", "--pubkey", "invalid", "--text", "a"],
+    )
+    .await;
+    assert_eq!(exit_code(res), 8);
+    let res = exec(&name, &["sign", "--kind", "1", "--tag", "invalid"]).await;
+    assert_eq!(exit_code(res), 8);
+
+    handle.abort();
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn test_unauthenticated() {
+    let name: String = unique_name();
+    let mock = MockProxy::new(&name, Keys::generate())
+        .unwrap()
+        .auth_token("secret");
+    let handle = mock.spawn().unwrap();
+
+    assert_eq!(exit_code(exec(&name, &["installed"]).await), 4);
+    let res = exec(&name, &["--auth-token", "secret", "installed"]).await;
+    assert_eq!(res.unwrap()["installed"], true);
+
+    handle.abort();
+}
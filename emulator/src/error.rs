@@ -0,0 +1,66 @@
+//! Emulator error
+
+use std::{fmt, io};
+
+use nostr::key;
+use nostr::nips::{nip19, nip49};
+use nostr_android_signer_mock::error::Error as MockError;
+
+/// Emulator error.
+#[derive(Debug)]
+pub enum Error {
+    /// I/O error
+    IO(io::Error),
+    /// Mock proxy error
+    Mock(MockError),
+    /// Keys error
+    Keys(key::Error),
+    /// NIP-19 error
+    Nip19(nip19::Error),
+    /// NIP-49 error
+    Nip49(nip49::Error),
+}
+
+impl std::error::Error for Error {}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::IO(e) => e.fmt(f),
+            Self::Mock(e) => e.fmt(f),
+            Self::Keys(e) => e.fmt(f),
+            Self::Nip19(e) => e.fmt(f),
+            Self::Nip49(e) => e.fmt(f),
+        }
+    }
+}
+
+impl From<io::Error> for Error {
+    fn from(e: io::Error) -> Self {
+        Self::IO(e)
+    }
+}
+
+impl From<MockError> for Error {
+    fn from(e: MockError) -> Self {
+        Self::Mock(e)
+    }
+}
+
+impl From<key::Error> for Error {
+    fn from(e: key::Error) -> Self {
+        Self::Keys(e)
+    }
+}
+
+impl From<nip19::Error> for Error {
+    fn from(e: nip19::Error) -> Self {
+        Self::Nip19(e)
+    }
+}
+
+impl From<nip49::Error> for Error {
+    fn from(e: nip49::Error) -> Self {
+        Self::Nip49(e)
+    }
+}
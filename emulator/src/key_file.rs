@@ -0,0 +1,28 @@
+//! Key file
+
+use std::fs;
+use std::path::Path;
+
+use nostr::nips::nip49::EncryptedSecretKey;
+use nostr::prelude::*;
+
+use crate::error::Error;
+
+const NCRYPTSEC_PREFIX: &str = "ncryptsec1";
+
+/// Load the keys from a file containing a `nsec`, hex or `ncryptsec` secret key.
+///
+/// For `ncryptsec`, the password is asked in the terminal.
+pub fn load(path: &Path) -> Result<Keys, Error> {
+    let content: String = fs::read_to_string(path)?;
+    let content: &str = content.trim();
+
+    if content.starts_with(NCRYPTSEC_PREFIX) {
+        let encrypted: EncryptedSecretKey = EncryptedSecretKey::from_bech32(content)?;
+        let password: String = rpassword::prompt_password("Key file password: ")?;
+        let secret_key: SecretKey = encrypted.decrypt(&password)?;
+        return Ok(Keys::new(secret_key));
+    }
+
+    Ok(Keys::parse(content)?)
+}
@@ -0,0 +1,80 @@
+//! Desktop emulator of the Android signer proxy (NIP-55)
+//!
+//! Serves the Android signer gRPC service on the same abstract socket of the Android proxy,
+//! backed by a local key file, so apps built on `nostr-android-signer` can run unchanged on desktop.
+//!
+//! Built on the mock proxy, with a terminal prompt as approval hook.
+
+#![forbid(unsafe_code)]
+#![warn(clippy::large_futures)]
+
+use std::path::PathBuf;
+use std::process::ExitCode;
+use std::sync::Arc;
+
+use clap::Parser;
+use nostr::prelude::*;
+use nostr_android_signer_mock::prelude::MockProxy;
+
+mod error;
+mod key_file;
+mod prompt;
+
+use self::error::Error;
+use self::prompt::Prompt;
+
+/// Desktop emulator of the Android signer proxy (NIP-55)
+#[derive(Debug, Parser)]
+#[command(name = "nostr-android-signer-emulator", version, about)]
+struct Args {
+    /// Unique name, must match the one used by the `AndroidSigner`
+    #[arg(long)]
+    name: String,
+    /// File containing the secret key (nsec, hex or ncryptsec)
+    #[arg(long)]
+    key_file: PathBuf,
+    /// Ask to approve or reject each request in the terminal
+    #[arg(long)]
+    prompt: bool,
+    /// Require an auth token, like the Android proxy
+    #[arg(long)]
+    auth_token: Option<String>,
+}
+
+#[tokio::main]
+async fn main() -> ExitCode {
+    let args: Args = Args::parse();
+
+    match run(args).await {
+        Ok(()) => ExitCode::SUCCESS,
+        Err(e) => {
+            eprintln!("Error: {e}");
+            ExitCode::FAILURE
+        }
+    }
+}
+
+async fn run(args: Args) -> Result<(), Error> {
+    let keys: Keys = key_file::load(&args.key_file)?;
+
+    eprintln!("Public key: {}", keys.public_key());
+    eprintln!("Listening on nip55_proxy_{}", args.name);
+
+    let mut proxy: MockProxy = MockProxy::new(&args.name, keys)?;
+
+    if args.prompt {
+        proxy = proxy.hook(Arc::new(Prompt::new()));
+    }
+
+    if let Some(auth_token) = args.auth_token {
+        proxy = proxy.auth_token(auth_token);
+    }
+
+    proxy
+        .run_until(async {
+            let _ = tokio::signal::ctrl_c().await;
+        })
+        .await?;
+
+    Ok(())
+}
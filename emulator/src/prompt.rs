@@ -0,0 +1,78 @@
+//! Terminal prompt
+
+use std::io::{self, BufRead};
+use std::thread;
+
+use nostr::util::BoxedFuture;
+use nostr_android_signer_mock::prelude::{ApprovalHook, Rpc};
+use tokio::sync::{Mutex, mpsc};
+
+/// Asks the user to approve or reject the requests in the terminal.
+///
+/// A single thread reads the terminal, so abandoned prompts don't leave a pending read behind.
+#[derive(Debug)]
+pub struct Prompt {
+    /// Lines read from the terminal
+    ///
+    /// Locked while asking, so one request is asked at a time.
+    lines: Mutex<mpsc::UnboundedReceiver<String>>,
+}
+
+impl Prompt {
+    pub fn new() -> Self {
+        let (tx, rx) = mpsc::unbounded_channel();
+
+        thread::spawn(move || {
+            for line in io::stdin().lock().lines() {
+                let Ok(line) = line else { break };
+                if tx.send(line).is_err() {
+                    break;
+                }
+            }
+        });
+
+        Self {
+            lines: Mutex::new(rx),
+        }
+    }
+}
+
+/// Tell the user that a prompt is no longer pending
+struct Abandoned {
+    answered: bool,
+}
+
+impl Drop for Abandoned {
+    fn drop(&mut self) {
+        if !self.answered {
+            eprintln!();
+            eprintln!("Request cancelled by the client");
+        }
+    }
+}
+
+impl ApprovalHook for Prompt {
+    fn approve(&self, _rpc: Rpc, description: String) -> BoxedFuture<'_, bool> {
+        Box::pin(async move {
+            let mut lines = self.lines.lock().await;
+
+            // Drop the answers typed while no request was pending
+            while lines.try_recv().is_ok() {}
+
+            eprintln!();
+            eprintln!("{description}");
+            eprint!("Approve? [y/N] ");
+
+            // The request may be dropped while waiting (i.e., the client gave up)
+            let mut abandoned = Abandoned { answered: false };
+            let answer: Option<String> = lines.recv().await;
+            abandoned.answered = true;
+
+            // Reject if the terminal has been closed
+            match answer {
+                Some(answer) => matches!(answer.trim().to_lowercase().as_str(), "y" | "yes"),
+                None => false,
+            }
+        })
+    }
+}
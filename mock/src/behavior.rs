@@ -0,0 +1,145 @@
+//! Scriptable behavior
+
+use std::time::Duration;
+
+use tonic::Code;
+
+/// Mock proxy RPC
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Rpc {
+    /// Is external signer installed
+    IsExternalSignerInstalled,
+    /// Get public key
+    GetPublicKey,
+    /// Logout
+    Logout,
+    /// Sign event
+    SignEvent,
+    /// Sign multiple events
+    SignEvents,
+    /// NIP-04 encrypt
+    Nip04Encrypt,
+    /// NIP-04 decrypt
+    Nip04Decrypt,
+    /// NIP-44 encrypt
+    Nip44Encrypt,
+    /// NIP-44 decrypt
+    Nip44Decrypt,
+    /// Decrypt zap event
+    DecryptZapEvent,
+}
+
+/// Outcome of a request
+///
+/// For [`Rpc::IsExternalSignerInstalled`], [`Outcome::Malformed`] and [`Outcome::Tampered`] reply `false`.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub enum Outcome {
+    /// Handle the request with the mock keys
+    #[default]
+    Approve,
+    /// Reject the request, like the user tapped "reject"
+    Reject,
+    /// Fail with a custom gRPC status
+    Fail {
+        /// Status code
+        code: Code,
+        /// Status message
+        message: String,
+    },
+    /// Reply with a value that can't be parsed (i.e., an invalid event JSON or public key)
+    Malformed,
+    /// Reply with a well-formed but different value (i.e., an event signed with a different content)
+    Tampered,
+    /// Reply with a valid event that differs from the requested one in a field
+    ///
+    /// Only for [`Rpc::SignEvent`] and [`Rpc::SignEvents`]: for the other RPCs it's the same as [`Outcome::Tampered`].
+    TamperedEvent(EventField),
+}
+
+impl Outcome {
+    pub(crate) fn is_tampered(&self) -> bool {
+        matches!(self, Self::Tampered | Self::TamperedEvent(..))
+    }
+}
+
+/// Field of a signed event
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum EventField {
+    /// Signed by other keys
+    PublicKey,
+    /// Creation timestamp
+    CreatedAt,
+    /// Kind
+    Kind,
+    /// Tags
+    Tags,
+    /// Content
+    Content,
+}
+
+/// Behavior of the mock proxy for a request
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Behavior {
+    pub(crate) delay: Option<Duration>,
+    pub(crate) outcome: Outcome,
+}
+
+impl Behavior {
+    /// Approve the request (default)
+    #[inline]
+    pub fn approve() -> Self {
+        Self::default()
+    }
+
+    /// Reject the request
+    #[inline]
+    pub fn reject() -> Self {
+        Self::outcome(Outcome::Reject)
+    }
+
+    /// Fail with a custom gRPC status
+    #[inline]
+    pub fn fail<S>(code: Code, message: S) -> Self
+    where
+        S: Into<String>,
+    {
+        Self::outcome(Outcome::Fail {
+            code,
+            message: message.into(),
+        })
+    }
+
+    /// Reply with a malformed value
+    #[inline]
+    pub fn malformed() -> Self {
+        Self::outcome(Outcome::Malformed)
+    }
+
+    /// Reply with a well-formed but tampered value
+    #[inline]
+    pub fn tampered() -> Self {
+        Self::outcome(Outcome::Tampered)
+    }
+
+    /// Reply with a valid event that differs from the requested one in a field
+    #[inline]
+    pub fn tampered_event(field: EventField) -> Self {
+        Self::outcome(Outcome::TamperedEvent(field))
+    }
+
+    /// New behavior with an outcome
+    #[inline]
+    pub fn outcome(outcome: Outcome) -> Self {
+        Self {
+            delay: None,
+            outcome,
+        }
+    }
+
+    /// Wait before replying, like an user reviewing the request
+    #[inline]
+    pub fn delay(mut self, delay: Duration) -> Self {
+        self.delay = Some(delay);
+        self
+    }
+}
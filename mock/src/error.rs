@@ -0,0 +1,35 @@
+//! Mock proxy error
+
+use std::{fmt, io};
+
+/// Mock proxy error.
+#[derive(Debug)]
+pub enum Error {
+    /// I/O error
+    IO(io::Error),
+    /// Tonic transport error
+    Transport(tonic::transport::Error),
+}
+
+impl std::error::Error for Error {}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::IO(e) => e.fmt(f),
+            Self::Transport(e) => e.fmt(f),
+        }
+    }
+}
+
+impl From<io::Error> for Error {
+    fn from(e: io::Error) -> Self {
+        Self::IO(e)
+    }
+}
+
+impl From<tonic::transport::Error> for Error {
+    fn from(e: tonic::transport::Error) -> Self {
+        Self::Transport(e)
+    }
+}
@@ -0,0 +1,25 @@
+//! Approval hook
+
+use std::fmt::Debug;
+
+use nostr::prelude::*;
+
+use crate::behavior::Rpc;
+
+/// Approve or reject the requests before handling them (i.e., by asking the user in a terminal)
+///
+/// Called only for the requests that would prompt the user on Android,
+/// after they have been validated and only if the scripted [`Behavior`](crate::behavior::Behavior) handles them.
+/// Rejected requests fail with `PermissionDenied`, like the Android proxy.
+pub trait ApprovalHook: Debug + Send + Sync {
+    /// Approve or reject a request
+    ///
+    /// The `description` is a human-readable summary of the request.
+    /// The future is dropped if the client gives up on the request.
+    fn approve(&self, rpc: Rpc, description: String) -> BoxedFuture<'_, bool>;
+}
+
+/// Describe an event to be signed
+pub(crate) fn describe_event(unsigned: &UnsignedEvent) -> String {
+    format!("  kind {}: {}", unsigned.kind, unsigned.content)
+}
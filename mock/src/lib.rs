@@ -0,0 +1,15 @@
+//! Mock Android signer proxy (NIP-55)
+//!
+//! Serves the Android signer gRPC service on the same abstract socket of the Android proxy,
+//! backed by local keys, so code built on `nostr-android-signer` can be tested off-device.
+
+#![forbid(unsafe_code)]
+#![warn(missing_docs)]
+#![warn(clippy::large_futures)]
+#![warn(rustdoc::bare_urls)]
+
+pub mod behavior;
+pub mod error;
+pub mod hook;
+pub mod prelude;
+pub mod proxy;
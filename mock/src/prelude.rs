@@ -0,0 +1,12 @@
+//! Prelude
+
+#![allow(unknown_lints)]
+#![allow(ambiguous_glob_reexports)]
+#![doc(hidden)]
+
+pub use tonic::Code;
+
+pub use crate::behavior::{self, *};
+pub use crate::error::{self, *};
+pub use crate::hook::{self, *};
+pub use crate::proxy::{self, *};
@@ -0,0 +1,530 @@
+//! Mock proxy
+
+use std::collections::HashMap;
+use std::future::Future;
+use std::os::unix::net::UnixListener as StdUnixListener;
+use std::path::Path;
+use std::sync::Arc;
+
+use nostr::nips::nip57;
+use nostr::prelude::*;
+use nostr_android_signer_proto::android_signer_server::{AndroidSigner, AndroidSignerServer};
+use nostr_android_signer_proto::{
+    AUTH_TOKEN_METADATA_KEY, DecryptZapEventReply, DecryptZapEventRequest, GetPublicKeyReply,
+    GetPublicKeyRequest, IsExternalSignerInstalledReply, IsExternalSignerInstalledRequest,
+    LogoutReply, LogoutRequest, Nip04DecryptReply, Nip04DecryptRequest, Nip04EncryptReply,
+    Nip04EncryptRequest, Nip44DecryptReply, Nip44DecryptRequest, Nip44EncryptReply,
+    Nip44EncryptRequest, SignEventReply, SignEventRequest, SignEventsError, SignEventsReply,
+    SignEventsRequest, SignEventsResult, sign_events_result,
+};
+use tokio::net::UnixListener as TokioUnixListener;
+use tokio::sync::RwLock;
+use tokio::task::JoinHandle;
+use tokio_stream::wrappers::UnixListenerStream;
+use tonic::transport::Server;
+use tonic::{Request, Response, Status};
+use uds::{UnixListenerExt, UnixSocketAddr};
+
+use crate::behavior::{Behavior, EventField, Outcome, Rpc};
+use crate::error::Error;
+use crate::hook::{self, ApprovalHook};
+
+const MALFORMED: &str = "malformed";
+const TAMPERED: &str = " (tampered)";
+
+type Behaviors = Arc<RwLock<HashMap<Rpc, Behavior>>>;
+
+/// Mock Android signer proxy.
+///
+/// All requests are approved by default. Use [`MockProxy::set_behavior`] to script them.
+#[derive(Debug, Clone)]
+pub struct MockProxy {
+    /// UNIX socket address
+    socket_addr: UnixSocketAddr,
+    /// Signer keys
+    keys: Keys,
+    /// Scripted behaviors
+    behaviors: Behaviors,
+    /// Required auth token
+    auth_token: Option<String>,
+    /// Approval hook
+    hook: Option<Arc<dyn ApprovalHook>>,
+}
+
+impl MockProxy {
+    /// Construct a new mock proxy.
+    ///
+    /// The `unique_name` must match the one used by the `AndroidSigner`.
+    pub fn new(unique_name: &str, keys: Keys) -> Result<Self, Error> {
+        let name: String = format!("nip55_proxy_{unique_name}");
+        let socket_addr: UnixSocketAddr = UnixSocketAddr::from_abstract(name.as_bytes())?;
+        Ok(Self::with_socket_addr(socket_addr, keys))
+    }
+
+    /// Construct a new mock proxy listening on a filesystem UNIX socket.
+    ///
+    /// Use with `AndroidSigner::with_path`. The socket file must not exist yet.
+    pub fn with_path<P>(path: P, keys: Keys) -> Result<Self, Error>
+    where
+        P: AsRef<Path>,
+    {
+        let socket_addr: UnixSocketAddr = UnixSocketAddr::from_path(path.as_ref())?;
+        Ok(Self::with_socket_addr(socket_addr, keys))
+    }
+
+    fn with_socket_addr(socket_addr: UnixSocketAddr, keys: Keys) -> Self {
+        Self {
+            socket_addr,
+            keys,
+            behaviors: Arc::new(RwLock::new(HashMap::new())),
+            auth_token: None,
+            hook: None,
+        }
+    }
+
+    /// Require an auth token, like the real proxy
+    ///
+    /// Requests without the token are refused with `Unauthenticated`.
+    #[inline]
+    pub fn auth_token<S>(mut self, token: S) -> Self
+    where
+        S: Into<String>,
+    {
+        self.auth_token = Some(token.into());
+        self
+    }
+
+    /// Ask a hook to approve or reject the requests
+    ///
+    /// Without a hook, all the requests handled by the scripted behavior are approved.
+    #[inline]
+    pub fn hook(mut self, hook: Arc<dyn ApprovalHook>) -> Self {
+        self.hook = Some(hook);
+        self
+    }
+
+    /// Get the signer keys
+    #[inline]
+    pub fn keys(&self) -> &Keys {
+        &self.keys
+    }
+
+    /// Set the behavior for an RPC
+    pub async fn set_behavior(&self, rpc: Rpc, behavior: Behavior) {
+        let mut behaviors = self.behaviors.write().await;
+        behaviors.insert(rpc, behavior);
+    }
+
+    /// Approve all the requests again
+    pub async fn reset(&self) {
+        let mut behaviors = self.behaviors.write().await;
+        behaviors.clear();
+    }
+
+    /// Run the proxy
+    pub async fn run(&self) -> Result<(), Error> {
+        self.run_until(std::future::pending()).await
+    }
+
+    /// Run the proxy until the `signal` completes.
+    ///
+    /// The pending requests are completed before returning.
+    pub async fn run_until<F>(&self, signal: F) -> Result<(), Error>
+    where
+        F: Future<Output = ()>,
+    {
+        let listener: TokioUnixListener = bind_socket(&self.socket_addr)?;
+        self.serve(listener, signal).await
+    }
+
+    /// Bind the socket and run the proxy in a background task.
+    ///
+    /// The socket is bound before returning, so clients can connect immediately.
+    /// Abort the returned handle to stop the proxy.
+    pub fn spawn(&self) -> Result<JoinHandle<Result<(), Error>>, Error> {
+        let listener: TokioUnixListener = bind_socket(&self.socket_addr)?;
+        let proxy: Self = self.clone();
+        Ok(tokio::spawn(async move {
+            proxy.serve(listener, std::future::pending()).await
+        }))
+    }
+
+    async fn serve<F>(&self, listener: TokioUnixListener, signal: F) -> Result<(), Error>
+    where
+        F: Future<Output = ()>,
+    {
+        let service = MockService {
+            keys: self.keys.clone(),
+            behaviors: self.behaviors.clone(),
+            hook: self.hook.clone(),
+        };
+
+        let auth_token: Option<String> = self.auth_token.clone();
+        let service = AndroidSignerServer::with_interceptor(service, move |req: Request<()>| {
+            if let Some(expected) = &auth_token {
+                let token = req.metadata().get(AUTH_TOKEN_METADATA_KEY);
+                if token.map(|t| t.as_bytes()) != Some(expected.as_bytes()) {
+                    return Err(Status::unauthenticated("Invalid auth token"));
+                }
+            }
+            Ok(req)
+        });
+
+        let stream: UnixListenerStream = UnixListenerStream::new(listener);
+
+        Server::builder()
+            .add_service(service)
+            .serve_with_incoming_shutdown(stream, signal)
+            .await?;
+
+        Ok(())
+    }
+}
+
+fn bind_socket(socket_addr: &UnixSocketAddr) -> Result<TokioUnixListener, Error> {
+    // Bind socket
+    let listener = StdUnixListener::bind_unix_addr(socket_addr)?;
+
+    // Moves the socket into nonblocking mode
+    listener.set_nonblocking(true)?;
+
+    // Convert to Tokio's async UnixListener
+    Ok(TokioUnixListener::from_std(listener)?)
+}
+
+struct MockService {
+    keys: Keys,
+    behaviors: Behaviors,
+    hook: Option<Arc<dyn ApprovalHook>>,
+}
+
+impl MockService {
+    /// Apply the behavior scripted for the RPC.
+    ///
+    /// Returns the outcome to apply if the request must be handled.
+    async fn outcome(&self, rpc: Rpc) -> Result<Outcome, Status> {
+        let behavior: Behavior = {
+            let behaviors = self.behaviors.read().await;
+            behaviors.get(&rpc).cloned().unwrap_or_default()
+        };
+
+        if let Some(delay) = behavior.delay {
+            tokio::time::sleep(delay).await;
+        }
+
+        match behavior.outcome {
+            Outcome::Reject => Err(Status::permission_denied("Request rejected")),
+            Outcome::Fail { code, message } => Err(Status::new(code, message)),
+            outcome => Ok(outcome),
+        }
+    }
+
+    /// Ask the hook, if any, to approve the request
+    async fn approve<F>(&self, rpc: Rpc, description: F) -> Result<(), Status>
+    where
+        F: FnOnce() -> String,
+    {
+        if let Some(hook) = &self.hook {
+            if !hook.approve(rpc, description()).await {
+                return Err(Status::permission_denied("Request rejected"));
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Check that the request is for the mock keys
+    fn check_current_user(&self, current_user_public_key: &str) -> Result<(), Status> {
+        let public_key: PublicKey = PublicKey::parse(current_user_public_key)
+            .map_err(|e| Status::invalid_argument(e.to_string()))?;
+
+        if public_key != self.keys.public_key() {
+            return Err(Status::invalid_argument("Unknown current user public key"));
+        }
+
+        Ok(())
+    }
+
+    fn other_public_key(&self, other_public_key: &str) -> Result<PublicKey, Status> {
+        PublicKey::parse(other_public_key).map_err(|e| Status::invalid_argument(e.to_string()))
+    }
+
+    fn sign(
+        &self,
+        unsigned: &str,
+        current_user_public_key: &str,
+        outcome: &Outcome,
+    ) -> Result<String, Status> {
+        self.check_current_user(current_user_public_key)?;
+
+        let mut unsigned: UnsignedEvent = UnsignedEvent::from_json(unsigned)
+            .map_err(|e| Status::invalid_argument(e.to_string()))?;
+        let mut keys: Keys = self.keys.clone();
+
+        let field: Option<EventField> = match outcome {
+            Outcome::Malformed => return Ok(String::from(MALFORMED)),
+            Outcome::Tampered => Some(EventField::Content),
+            Outcome::TamperedEvent(field) => Some(*field),
+            _ => None,
+        };
+
+        if let Some(field) = field {
+            match field {
+                EventField::PublicKey => {
+                    keys = Keys::generate();
+                    unsigned.pubkey = keys.public_key();
+                }
+                EventField::CreatedAt => unsigned.created_at = unsigned.created_at + 1,
+                EventField::Kind => unsigned.kind = Kind::from(unsigned.kind.as_u16() ^ 1),
+                EventField::Tags => unsigned.tags.push(Tag::hashtag("tampered")),
+                EventField::Content => unsigned.content.push_str(TAMPERED),
+            }
+            unsigned.id = None;
+        }
+
+        let event: Event = unsigned
+            .sign_with_keys(&keys)
+            .map_err(|e| Status::invalid_argument(e.to_string()))?;
+
+        Ok(event.as_json())
+    }
+}
+
+#[tonic::async_trait]
+impl AndroidSigner for MockService {
+    async fn is_external_signer_installed(
+        &self,
+        _request: Request<IsExternalSignerInstalledRequest>,
+    ) -> Result<Response<IsExternalSignerInstalledReply>, Status> {
+        let outcome: Outcome = self.outcome(Rpc::IsExternalSignerInstalled).await?;
+        Ok(Response::new(IsExternalSignerInstalledReply {
+            installed: outcome == Outcome::Approve,
+        }))
+    }
+
+    async fn get_public_key(
+        &self,
+        _request: Request<GetPublicKeyRequest>,
+    ) -> Result<Response<GetPublicKeyReply>, Status> {
+        let outcome: Outcome = self.outcome(Rpc::GetPublicKey).await?;
+        self.approve(Rpc::GetPublicKey, || String::from("Share the public key"))
+            .await?;
+        let public_key: String = match outcome {
+            Outcome::Malformed => String::from(MALFORMED),
+            outcome if outcome.is_tampered() => Keys::generate().public_key().to_hex(),
+            _ => self.keys.public_key().to_hex(),
+        };
+        Ok(Response::new(GetPublicKeyReply { public_key }))
+    }
+
+    async fn logout(
+        &self,
+        _request: Request<LogoutRequest>,
+    ) -> Result<Response<LogoutReply>, Status> {
+        self.outcome(Rpc::Logout).await?;
+        Ok(Response::new(LogoutReply {}))
+    }
+
+    async fn sign_event(
+        &self,
+        request: Request<SignEventRequest>,
+    ) -> Result<Response<SignEventReply>, Status> {
+        let req: SignEventRequest = request.into_inner();
+        let outcome: Outcome = self.outcome(Rpc::SignEvent).await?;
+        self.check_current_user(&req.current_user_public_key)?;
+        let unsigned: UnsignedEvent = UnsignedEvent::from_json(&req.unsigned_event)
+            .map_err(|e| Status::invalid_argument(e.to_string()))?;
+        self.approve(Rpc::SignEvent, || {
+            format!("Sign event\n{}", hook::describe_event(&unsigned))
+        })
+        .await?;
+        let event: String =
+            self.sign(&req.unsigned_event, &req.current_user_public_key, &outcome)?;
+        Ok(Response::new(SignEventReply { event }))
+    }
+
+    async fn sign_events(
+        &self,
+        request: Request<SignEventsRequest>,
+    ) -> Result<Response<SignEventsReply>, Status> {
+        let req: SignEventsRequest = request.into_inner();
+        let outcome: Outcome = self.outcome(Rpc::SignEvents).await?;
+
+        // A single approval for the whole batch
+        self.approve(Rpc::SignEvents, || {
+            let description: Vec<String> = req
+                .events
+                .iter()
+                .filter(|e| self.check_current_user(&e.current_user_public_key).is_ok())
+                .filter_map(|e| UnsignedEvent::from_json(&e.unsigned_event).ok())
+                .map(|unsigned| hook::describe_event(&unsigned))
+                .collect();
+            format!(
+                "Sign {} events\n{}",
+                description.len(),
+                description.join("\n")
+            )
+        })
+        .await?;
+
+        let results: Vec<SignEventsResult> = req
+            .events
+            .into_iter()
+            .map(|e| SignEventsResult {
+                result: Some(
+                    match self.sign(&e.unsigned_event, &e.current_user_public_key, &outcome) {
+                        Ok(event) => sign_events_result::Result::Event(event),
+                        Err(status) => sign_events_result::Result::Error(SignEventsError {
+                            code: status.code() as i32,
+                            message: status.message().to_string(),
+                            policy_rule: None,
+                        }),
+                    },
+                ),
+            })
+            .collect();
+        Ok(Response::new(SignEventsReply { results }))
+    }
+
+    async fn nip04_encrypt(
+        &self,
+        request: Request<Nip04EncryptRequest>,
+    ) -> Result<Response<Nip04EncryptReply>, Status> {
+        let req: Nip04EncryptRequest = request.into_inner();
+        let outcome: Outcome = self.outcome(Rpc::Nip04Encrypt).await?;
+        self.check_current_user(&req.current_user_public_key)?;
+        let public_key: PublicKey = self.other_public_key(&req.other_public_key)?;
+        self.approve(Rpc::Nip04Encrypt, || {
+            format!("NIP-04 encrypt for {public_key}")
+        })
+        .await?;
+        let ciphertext: String = match outcome {
+            Outcome::Malformed => String::from(MALFORMED),
+            outcome => {
+                let mut plaintext: String = req.plaintext;
+                if outcome.is_tampered() {
+                    plaintext.push_str(TAMPERED);
+                }
+                self.keys
+                    .nip04_encrypt(&public_key, &plaintext)
+                    .await
+                    .map_err(|e| Status::invalid_argument(e.to_string()))?
+            }
+        };
+        Ok(Response::new(Nip04EncryptReply { ciphertext }))
+    }
+
+    async fn nip04_decrypt(
+        &self,
+        request: Request<Nip04DecryptRequest>,
+    ) -> Result<Response<Nip04DecryptReply>, Status> {
+        let req: Nip04DecryptRequest = request.into_inner();
+        let outcome: Outcome = self.outcome(Rpc::Nip04Decrypt).await?;
+        self.check_current_user(&req.current_user_public_key)?;
+        let public_key: PublicKey = self.other_public_key(&req.other_public_key)?;
+        self.approve(Rpc::Nip04Decrypt, || {
+            format!("NIP-04 decrypt from {public_key}")
+        })
+        .await?;
+        let plaintext: String = match outcome {
+            Outcome::Malformed => String::from(MALFORMED),
+            outcome => {
+                let mut plaintext: String = self
+                    .keys
+                    .nip04_decrypt(&public_key, &req.ciphertext)
+                    .await
+                    .map_err(|e| Status::invalid_argument(e.to_string()))?;
+                if outcome.is_tampered() {
+                    plaintext.push_str(TAMPERED);
+                }
+                plaintext
+            }
+        };
+        Ok(Response::new(Nip04DecryptReply { plaintext }))
+    }
+
+    async fn nip44_encrypt(
+        &self,
+        request: Request<Nip44EncryptRequest>,
+    ) -> Result<Response<Nip44EncryptReply>, Status> {
+        let req: Nip44EncryptRequest = request.into_inner();
+        let outcome: Outcome = self.outcome(Rpc::Nip44Encrypt).await?;
+        self.check_current_user(&req.current_user_public_key)?;
+        let public_key: PublicKey = self.other_public_key(&req.other_public_key)?;
+        self.approve(Rpc::Nip44Encrypt, || {
+            format!("NIP-44 encrypt for {public_key}")
+        })
+        .await?;
+        let ciphertext: String = match outcome {
+            Outcome::Malformed => String::from(MALFORMED),
+            outcome => {
+                let mut plaintext: String = req.plaintext;
+                if outcome.is_tampered() {
+                    plaintext.push_str(TAMPERED);
+                }
+                self.keys
+                    .nip44_encrypt(&public_key, &plaintext)
+                    .await
+                    .map_err(|e| Status::invalid_argument(e.to_string()))?
+            }
+        };
+        Ok(Response::new(Nip44EncryptReply { ciphertext }))
+    }
+
+    async fn nip44_decrypt(
+        &self,
+        request: Request<Nip44DecryptRequest>,
+    ) -> Result<Response<Nip44DecryptReply>, Status> {
+        let req: Nip44DecryptRequest = request.into_inner();
+        let outcome: Outcome = self.outcome(Rpc::Nip44Decrypt).await?;
+        self.check_current_user(&req.current_user_public_key)?;
+        let public_key: PublicKey = self.other_public_key(&req.other_public_key)?;
+        self.approve(Rpc::Nip44Decrypt, || {
+            format!("NIP-44 decrypt from {public_key}")
+        })
+        .await?;
+        let plaintext: String = match outcome {
+            Outcome::Malformed => String::from(MALFORMED),
+            outcome => {
+                let mut plaintext: String = self
+                    .keys
+                    .nip44_decrypt(&public_key, &req.ciphertext)
+                    .await
+                    .map_err(|e| Status::invalid_argument(e.to_string()))?;
+                if outcome.is_tampered() {
+                    plaintext.push_str(TAMPERED);
+                }
+                plaintext
+            }
+        };
+        Ok(Response::new(Nip44DecryptReply { plaintext }))
+    }
+
+    async fn decrypt_zap_event(
+        &self,
+        request: Request<DecryptZapEventRequest>,
+    ) -> Result<Response<DecryptZapEventReply>, Status> {
+        let req: DecryptZapEventRequest = request.into_inner();
+        let outcome: Outcome = self.outcome(Rpc::DecryptZapEvent).await?;
+        self.check_current_user(&req.current_user_public_key)?;
+        let event: Event =
+            Event::from_json(&req.event).map_err(|e| Status::invalid_argument(e.to_string()))?;
+        self.approve(Rpc::DecryptZapEvent, || {
+            format!("Decrypt zap event {} from {}", event.id, event.pubkey)
+        })
+        .await?;
+        let event: String = match outcome {
+            Outcome::Malformed => String::from(MALFORMED),
+            outcome => {
+                let mut zap_request: Event =
+                    nip57::decrypt_received_private_zap_message(self.keys.secret_key(), &event)
+                        .map_err(|e| Status::invalid_argument(e.to_string()))?;
+                if outcome.is_tampered() {
+                    zap_request.content.push_str(TAMPERED);
+                }
+                zap_request.as_json()
+            }
+        };
+        Ok(Response::new(DecryptZapEventReply { event }))
+    }
+}
@@ -1 +1,7 @@
 tonic::include_proto!("android_signer");
+
+/// Metadata key of the shared-secret token that authenticates the clients to the proxy
+pub const AUTH_TOKEN_METADATA_KEY: &str = "x-nip55-proxy-token";
+
+/// Binary metadata key of the policy rule that denied a request
+pub const POLICY_RULE_METADATA_KEY: &str = "x-nip55-proxy-policy-rule-bin";
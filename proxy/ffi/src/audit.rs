@@ -0,0 +1,296 @@
+//! Audit log
+//!
+//! Records every request handled by the proxy, without the plaintexts or the ciphertexts.
+
+use std::collections::VecDeque;
+use std::fs::{File, OpenOptions};
+use std::io::{self, LineWriter, Write};
+use std::sync::mpsc::{self, Sender};
+use std::sync::{Mutex, PoisonError};
+use std::thread;
+use std::time::{Instant, SystemTime, UNIX_EPOCH};
+
+use nostr::{EventId, PublicKey};
+use nostr_android_signer_proto::POLICY_RULE_METADATA_KEY;
+use serde_json::json;
+use tonic::{Code, Status};
+use uniffi::{Enum, Record};
+
+use crate::error::AndroidSignerProxyError;
+
+/// Audited method
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Enum)]
+pub enum AuditMethod {
+    IsExternalSignerInstalled,
+    GetPublicKey,
+    Logout,
+    SignEvent,
+    SignEvents,
+    Nip04Encrypt,
+    Nip04Decrypt,
+    Nip44Encrypt,
+    Nip44Decrypt,
+    DecryptZapEvent,
+}
+
+impl AuditMethod {
+    fn as_str(&self) -> &str {
+        match self {
+            Self::IsExternalSignerInstalled => "is_external_signer_installed",
+            Self::GetPublicKey => "get_public_key",
+            Self::Logout => "logout",
+            Self::SignEvent => "sign_event",
+            Self::SignEvents => "sign_events",
+            Self::Nip04Encrypt => "nip04_encrypt",
+            Self::Nip04Decrypt => "nip04_decrypt",
+            Self::Nip44Encrypt => "nip44_encrypt",
+            Self::Nip44Decrypt => "nip44_decrypt",
+            Self::DecryptZapEvent => "decrypt_zap_event",
+        }
+    }
+}
+
+/// Outcome of an audited request
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Enum)]
+pub enum AuditOutcome {
+    /// The request succeeded
+    Success,
+    /// The user rejected the request
+    Rejected,
+    /// The request has been denied by a policy rule
+    PolicyDenied,
+    /// The request failed
+    Failed,
+}
+
+impl AuditOutcome {
+    fn as_str(&self) -> &str {
+        match self {
+            Self::Success => "success",
+            Self::Rejected => "rejected",
+            Self::PolicyDenied => "policy_denied",
+            Self::Failed => "failed",
+        }
+    }
+}
+
+/// Audit record
+#[derive(Debug, Clone, Record)]
+pub struct AuditRecord {
+    /// When the request has been received, as UNIX timestamp in milliseconds
+    pub timestamp_ms: u64,
+    pub method: AuditMethod,
+    /// Event kind
+    pub kind: Option<u16>,
+    /// Event ID
+    pub event_id: Option<String>,
+    /// Counterparty public key (hex)
+    pub counterparty: Option<String>,
+    pub outcome: AuditOutcome,
+    /// Time taken to handle the request, in milliseconds
+    pub latency_ms: u64,
+    /// Error class (i.e., `timeout`, `invalid_argument`), if the request didn't succeed
+    pub error_class: Option<String>,
+}
+
+impl AuditRecord {
+    fn as_json(&self) -> String {
+        json!({
+            "timestamp_ms": self.timestamp_ms,
+            "method": self.method.as_str(),
+            "kind": self.kind,
+            "event_id": self.event_id,
+            "counterparty": self.counterparty,
+            "outcome": self.outcome.as_str(),
+            "latency_ms": self.latency_ms,
+            "error_class": self.error_class,
+        })
+        .to_string()
+    }
+}
+
+/// Audit record being built while handling a request
+#[derive(Debug)]
+pub(crate) struct AuditEntry {
+    method: AuditMethod,
+    timestamp_ms: u64,
+    started_at: Instant,
+    pub kind: Option<u16>,
+    pub event_id: Option<EventId>,
+    pub counterparty: Option<PublicKey>,
+}
+
+impl AuditEntry {
+    pub(crate) fn new(method: AuditMethod) -> Self {
+        let timestamp_ms: u64 = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map(|d| d.as_millis() as u64)
+            .unwrap_or_default();
+
+        Self {
+            method,
+            timestamp_ms,
+            started_at: Instant::now(),
+            kind: None,
+            event_id: None,
+            counterparty: None,
+        }
+    }
+
+    /// Same request, for another event of a batch
+    pub(crate) fn split(&self) -> Self {
+        Self {
+            method: self.method,
+            timestamp_ms: self.timestamp_ms,
+            started_at: self.started_at,
+            kind: None,
+            event_id: None,
+            counterparty: None,
+        }
+    }
+
+    /// Build the record
+    pub(crate) fn finish(self, error: Option<ErrorClass>) -> AuditRecord {
+        let (outcome, error_class): (AuditOutcome, Option<String>) = match error {
+            Some(error) => (error.outcome(), Some(error.0.to_string())),
+            None => (AuditOutcome::Success, None),
+        };
+
+        AuditRecord {
+            timestamp_ms: self.timestamp_ms,
+            method: self.method,
+            kind: self.kind,
+            event_id: self.event_id.map(|id| id.to_hex()),
+            counterparty: self.counterparty.map(|pk| pk.to_hex()),
+            outcome,
+            latency_ms: self.started_at.elapsed().as_millis() as u64,
+            error_class,
+        }
+    }
+}
+
+/// Error class
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) struct ErrorClass(&'static str);
+
+impl ErrorClass {
+    const REJECTED: Self = Self("rejected");
+    const POLICY_DENIED: Self = Self("policy_denied");
+
+    /// Classify an error by its status code
+    pub(crate) fn new(code: Code, policy_denied: bool) -> Self {
+        match code {
+            Code::PermissionDenied if policy_denied => Self::POLICY_DENIED,
+            Code::PermissionDenied => Self::REJECTED,
+            Code::FailedPrecondition => Self("signer_not_installed"),
+            Code::InvalidArgument => Self("invalid_argument"),
+            Code::Unimplemented => Self("unsupported"),
+            Code::DataLoss => Self("invalid_signer_response"),
+            Code::DeadlineExceeded => Self("timeout"),
+            Code::Cancelled => Self("cancelled"),
+            Code::Unavailable => Self("unavailable"),
+            Code::Unauthenticated => Self("unauthenticated"),
+            _ => Self("internal"),
+        }
+    }
+
+    fn outcome(&self) -> AuditOutcome {
+        match *self {
+            Self::REJECTED => AuditOutcome::Rejected,
+            Self::POLICY_DENIED => AuditOutcome::PolicyDenied,
+            _ => AuditOutcome::Failed,
+        }
+    }
+}
+
+impl From<&Status> for ErrorClass {
+    fn from(status: &Status) -> Self {
+        let policy_denied: bool = status
+            .metadata()
+            .get_bin(POLICY_RULE_METADATA_KEY)
+            .is_some();
+        Self::new(status.code(), policy_denied)
+    }
+}
+
+impl From<&AndroidSignerProxyError> for ErrorClass {
+    fn from(e: &AndroidSignerProxyError) -> Self {
+        Self::new(e.code(), e.policy_rule().is_some())
+    }
+}
+
+/// Bounded in-memory audit log, optionally persisted to a JSON-lines file
+#[derive(Debug)]
+pub(crate) struct AuditLog {
+    records: Mutex<VecDeque<AuditRecord>>,
+    capacity: usize,
+    /// Lines to append to the file, written by a dedicated thread
+    file: Option<Sender<String>>,
+}
+
+impl AuditLog {
+    pub(crate) fn new(
+        capacity: usize,
+        path: Option<&str>,
+    ) -> Result<Self, AndroidSignerProxyError> {
+        let file: Option<Sender<String>> = match path {
+            Some(path) => {
+                let file: File = OpenOptions::new().create(true).append(true).open(path)?;
+                Some(spawn_writer(file)?)
+            }
+            None => None,
+        };
+
+        Ok(Self {
+            records: Mutex::new(VecDeque::new()),
+            capacity,
+            file,
+        })
+    }
+
+    /// Add a record, dropping the oldest one if the log is full
+    pub(crate) fn record(&self, record: AuditRecord) {
+        // Best effort: a failing write must not fail the request
+        if let Some(file) = &self.file {
+            let _ = file.send(record.as_json());
+        }
+
+        if self.capacity == 0 {
+            return;
+        }
+
+        let mut records = self.records.lock().unwrap_or_else(PoisonError::into_inner);
+        if records.len() >= self.capacity {
+            records.pop_front();
+        }
+        records.push_back(record);
+    }
+
+    /// Get the records, from the oldest
+    pub(crate) fn records(&self) -> Vec<AuditRecord> {
+        let records = self.records.lock().unwrap_or_else(PoisonError::into_inner);
+        records.iter().cloned().collect()
+    }
+
+    /// Clear the in-memory records
+    pub(crate) fn clear(&self) {
+        let mut records = self.records.lock().unwrap_or_else(PoisonError::into_inner);
+        records.clear();
+    }
+}
+
+/// Append the lines to the file on a dedicated thread, so the request handlers never block on the disk
+///
+/// The thread stops once the log is dropped.
+fn spawn_writer(file: File) -> Result<Sender<String>, io::Error> {
+    let (sender, receiver) = mpsc::channel::<String>();
+    thread::Builder::new()
+        .name(String::from("audit-log"))
+        .spawn(move || {
+            let mut file: LineWriter<File> = LineWriter::new(file);
+            for line in receiver {
+                let _ = writeln!(file, "{line}");
+            }
+        })?;
+    Ok(sender)
+}
@@ -0,0 +1,177 @@
+//! Request coalescing
+//!
+//! Identical concurrent requests share a single callback invocation.
+
+use std::collections::HashMap;
+use std::future::Future;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex, PoisonError, Weak};
+
+use prost::Message;
+use tokio::sync::watch;
+use tokio::task::AbortHandle;
+
+use crate::error::AndroidSignerProxyError;
+
+type Reply<T> = Option<Result<T, AndroidSignerProxyError>>;
+type InFlightMap<T> = Arc<Mutex<HashMap<CoalesceKey, InFlight<T>>>>;
+
+/// Key of a request: the RPC name and the encoded request
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub(crate) struct CoalesceKey {
+    rpc: &'static str,
+    request: Vec<u8>,
+}
+
+impl CoalesceKey {
+    pub(crate) fn new<M>(rpc: &'static str, request: &M) -> Self
+    where
+        M: Message,
+    {
+        Self {
+            rpc,
+            request: request.encode_to_vec(),
+        }
+    }
+}
+
+/// An in-flight callback invocation
+#[derive(Debug)]
+struct InFlight<T> {
+    /// Identifies the invocation, since an entry may be replaced while its task is finishing
+    generation: u64,
+    rx: watch::Receiver<Reply<T>>,
+    /// Only the waiters own the invocation
+    waiters: Weak<Waiters<T>>,
+}
+
+/// Shared by the waiters of an invocation: when the last one goes away, the callback is aborted.
+#[derive(Debug)]
+struct Waiters<T> {
+    key: CoalesceKey,
+    task: AbortHandle,
+    in_flight: InFlightMap<T>,
+}
+
+impl<T> Drop for Waiters<T> {
+    fn drop(&mut self) {
+        self.task.abort();
+
+        // Remove the entry, unless it has been replaced by a new invocation
+        let mut in_flight = self
+            .in_flight
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner);
+        if in_flight
+            .get(&self.key)
+            .is_some_and(|entry| entry.waiters.strong_count() == 0)
+        {
+            in_flight.remove(&self.key);
+        }
+    }
+}
+
+/// In-flight requests with the same reply type
+#[derive(Debug)]
+pub(crate) struct Coalescer<T> {
+    in_flight: InFlightMap<T>,
+    next_generation: AtomicU64,
+}
+
+impl<T> Default for Coalescer<T> {
+    fn default() -> Self {
+        Self {
+            in_flight: Arc::new(Mutex::new(HashMap::new())),
+            next_generation: AtomicU64::new(0),
+        }
+    }
+}
+
+impl<T> Coalescer<T>
+where
+    T: Clone + Send + Sync + 'static,
+{
+    /// Run the future, or wait for the reply of an identical in-flight request.
+    ///
+    /// The future runs in a separate task, so it completes even if the first caller goes away,
+    /// but it's aborted as soon as no caller is waiting for it anymore
+    /// (i.e., all the clients gave up or the proxy has been stopped).
+    pub(crate) async fn run<F>(
+        &self,
+        key: CoalesceKey,
+        future: F,
+    ) -> Result<T, AndroidSignerProxyError>
+    where
+        F: Future<Output = Result<T, AndroidSignerProxyError>> + Send + 'static,
+    {
+        let (mut rx, _waiters): (watch::Receiver<Reply<T>>, Arc<Waiters<T>>) = {
+            let mut in_flight = self
+                .in_flight
+                .lock()
+                .unwrap_or_else(PoisonError::into_inner);
+
+            // If the sender is gone (i.e., the task panicked), invoke the callback again
+            let joined = in_flight
+                .get(&key)
+                .filter(|entry| entry.rx.has_changed().is_ok())
+                .and_then(|entry| Some((entry.rx.clone(), entry.waiters.upgrade()?)));
+
+            match joined {
+                Some(joined) => joined,
+                None => {
+                    let (tx, rx) = watch::channel(None);
+                    let generation: u64 = self.next_generation.fetch_add(1, Ordering::Relaxed);
+
+                    let task: AbortHandle = tokio::spawn({
+                        let in_flight = self.in_flight.clone();
+                        let key = key.clone();
+                        async move {
+                            let res: Result<T, AndroidSignerProxyError> = future.await;
+
+                            // Remove before replying, so later requests invoke the callback again
+                            let mut in_flight =
+                                in_flight.lock().unwrap_or_else(PoisonError::into_inner);
+                            if in_flight
+                                .get(&key)
+                                .is_some_and(|entry| entry.generation == generation)
+                            {
+                                in_flight.remove(&key);
+                            }
+
+                            tx.send_replace(Some(res));
+                        }
+                    })
+                    .abort_handle();
+
+                    let waiters: Arc<Waiters<T>> = Arc::new(Waiters {
+                        key: key.clone(),
+                        task,
+                        in_flight: self.in_flight.clone(),
+                    });
+                    in_flight.insert(
+                        key,
+                        InFlight {
+                            generation,
+                            rx: rx.clone(),
+                            waiters: Arc::downgrade(&waiters),
+                        },
+                    );
+
+                    (rx, waiters)
+                }
+            }
+        };
+
+        let reply = rx
+            .wait_for(Option::is_some)
+            .await
+            .map_err(|_| AndroidSignerProxyError::Callback(String::from("Request dropped")))?;
+
+        match reply.as_ref() {
+            Some(res) => res.clone(),
+            None => Err(AndroidSignerProxyError::Callback(String::from(
+                "Missing reply",
+            ))),
+        }
+    }
+}
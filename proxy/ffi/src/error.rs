@@ -1,13 +1,31 @@
 use std::fmt;
 
-use tonic::Status;
-use uniffi::Error;
+use nostr_android_signer_proto::POLICY_RULE_METADATA_KEY;
+use tonic::metadata::BinaryMetadataValue;
+use tonic::{Code, Status};
+use uniffi::{Error, UnexpectedUniFFICallbackError};
 
-#[derive(Debug, Error)]
+#[derive(Debug, Clone, Error)]
 pub enum AndroidSignerProxyError {
     IO(String),
     Transport(String),
     Callback(String),
+    /// The user rejected the request
+    Rejected,
+    /// The request has been denied by a policy rule
+    PolicyDenied(String),
+    /// No external signer app is installed
+    SignerNotInstalled,
+    /// The request has invalid arguments
+    InvalidArgument(String),
+    /// The request is not supported by the signer
+    Unsupported(String),
+    /// The signer app replied with an invalid response
+    InvalidSignerResponse(String),
+    /// The signer didn't reply in time
+    Timeout,
+    /// The request has been cancelled
+    Cancelled,
 }
 
 impl fmt::Display for AndroidSignerProxyError {
@@ -16,6 +34,38 @@ impl fmt::Display for AndroidSignerProxyError {
             Self::IO(e) => f.write_str(e),
             Self::Transport(e) => f.write_str(e),
             Self::Callback(e) => f.write_str(e),
+            Self::Rejected => f.write_str("Request rejected"),
+            Self::PolicyDenied(rule) => write!(f, "Request denied by policy rule '{rule}'"),
+            Self::SignerNotInstalled => f.write_str("Signer not installed"),
+            Self::InvalidArgument(e) => f.write_str(e),
+            Self::Unsupported(e) => f.write_str(e),
+            Self::InvalidSignerResponse(e) => write!(f, "Invalid signer response: {e}"),
+            Self::Timeout => f.write_str("Timeout"),
+            Self::Cancelled => f.write_str("Request cancelled"),
+        }
+    }
+}
+
+impl AndroidSignerProxyError {
+    /// gRPC status code
+    pub(crate) fn code(&self) -> Code {
+        match self {
+            Self::IO(..) | Self::Transport(..) | Self::Callback(..) => Code::Internal,
+            Self::Rejected | Self::PolicyDenied(..) => Code::PermissionDenied,
+            Self::SignerNotInstalled => Code::FailedPrecondition,
+            Self::InvalidArgument(..) => Code::InvalidArgument,
+            Self::Unsupported(..) => Code::Unimplemented,
+            Self::InvalidSignerResponse(..) => Code::DataLoss,
+            Self::Timeout => Code::DeadlineExceeded,
+            Self::Cancelled => Code::Cancelled,
+        }
+    }
+
+    /// Policy rule that denied the request
+    pub(crate) fn policy_rule(&self) -> Option<&str> {
+        match self {
+            Self::PolicyDenied(rule) => Some(rule),
+            _ => None,
         }
     }
 }
@@ -32,8 +82,24 @@ impl From<tonic::transport::Error> for AndroidSignerProxyError {
     }
 }
 
+impl From<UnexpectedUniFFICallbackError> for AndroidSignerProxyError {
+    fn from(e: UnexpectedUniFFICallbackError) -> Self {
+        Self::Callback(e.reason)
+    }
+}
+
 impl From<AndroidSignerProxyError> for Status {
     fn from(e: AndroidSignerProxyError) -> Self {
-        Status::internal(e.to_string())
+        let mut status: Status = Status::new(e.code(), e.to_string());
+
+        // Let the clients distinguish a policy denial from a user rejection
+        if let Some(rule) = e.policy_rule() {
+            status.metadata_mut().insert_bin(
+                POLICY_RULE_METADATA_KEY,
+                BinaryMetadataValue::from_bytes(rule.as_bytes()),
+            );
+        }
+
+        status
     }
 }
@@ -1,7 +1,16 @@
 #![forbid(unsafe_code)]
 #![warn(clippy::large_futures)]
 
+mod audit;
+mod coalesce;
 mod error;
+mod options;
+mod policy;
+mod rejection;
 mod server;
+#[cfg(test)]
+mod tests;
+mod validate;
+mod verify;
 
 uniffi::setup_scaffolding!("nostr_android_signer_proxy");
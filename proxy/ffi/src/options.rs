@@ -0,0 +1,164 @@
+use std::fmt;
+use std::sync::Arc;
+use std::time::Duration;
+
+use uniffi::Object;
+
+use crate::policy::Policy;
+
+const DEFAULT_SHUTDOWN_GRACE_PERIOD: Duration = Duration::from_secs(5);
+const DEFAULT_AUDIT_CAPACITY: u32 = 1000;
+
+/// Proxy options
+#[derive(Clone, Object)]
+pub struct NostrAndroidSignerProxyOptions {
+    pub(crate) allowed_uids: Vec<u32>,
+    pub(crate) allowed_pids: Vec<i32>,
+    pub(crate) auth_token: Option<String>,
+    pub(crate) shutdown_grace_period: Duration,
+    pub(crate) policy: Policy,
+    pub(crate) audit_capacity: u32,
+    pub(crate) audit_path: Option<String>,
+    pub(crate) rejection_ttl: Duration,
+    pub(crate) tcp_port: Option<u16>,
+    pub(crate) socket_mode: Option<u32>,
+}
+
+impl fmt::Debug for NostrAndroidSignerProxyOptions {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("NostrAndroidSignerProxyOptions")
+            .field("allowed_uids", &self.allowed_uids)
+            .field("allowed_pids", &self.allowed_pids)
+            // Never print the token
+            .field(
+                "auth_token",
+                &self.auth_token.as_ref().map(|_| "<redacted>"),
+            )
+            .field("shutdown_grace_period", &self.shutdown_grace_period)
+            .field("policy", &self.policy)
+            .field("audit_capacity", &self.audit_capacity)
+            .field("audit_path", &self.audit_path)
+            .field("rejection_ttl", &self.rejection_ttl)
+            .field("tcp_port", &self.tcp_port)
+            .field("socket_mode", &self.socket_mode)
+            .finish()
+    }
+}
+
+impl Default for NostrAndroidSignerProxyOptions {
+    fn default() -> Self {
+        Self {
+            allowed_uids: Vec::new(),
+            allowed_pids: Vec::new(),
+            auth_token: None,
+            shutdown_grace_period: DEFAULT_SHUTDOWN_GRACE_PERIOD,
+            policy: Policy::default(),
+            audit_capacity: DEFAULT_AUDIT_CAPACITY,
+            audit_path: None,
+            rejection_ttl: Duration::ZERO,
+            tcp_port: None,
+            socket_mode: None,
+        }
+    }
+}
+
+#[uniffi::export]
+impl NostrAndroidSignerProxyOptions {
+    #[uniffi::constructor]
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// UIDs allowed to connect to the proxy
+    ///
+    /// If empty (default), only the UID of the proxy process is allowed.
+    pub fn allowed_uids(self: Arc<Self>, uids: Vec<u32>) -> Self {
+        let mut builder = Arc::unwrap_or_clone(self);
+        builder.allowed_uids = uids;
+        builder
+    }
+
+    /// PIDs allowed to connect to the proxy
+    ///
+    /// If empty (default), any PID of the allowed UIDs is accepted.
+    pub fn allowed_pids(self: Arc<Self>, pids: Vec<i32>) -> Self {
+        let mut builder = Arc::unwrap_or_clone(self);
+        builder.allowed_pids = pids;
+        builder
+    }
+
+    /// Token that clients must send with every request
+    ///
+    /// Requests without a valid token are refused with `Unauthenticated`,
+    /// before reaching the callback.
+    pub fn auth_token(self: Arc<Self>, token: String) -> Self {
+        let mut builder = Arc::unwrap_or_clone(self);
+        builder.auth_token = Some(token);
+        builder
+    }
+
+    /// How long pending requests can take to complete after a shutdown (default: 5 secs)
+    pub fn shutdown_grace_period(self: Arc<Self>, grace_period: Duration) -> Self {
+        let mut builder = Arc::unwrap_or_clone(self);
+        builder.shutdown_grace_period = grace_period;
+        builder
+    }
+
+    /// Request policy (default: allow all)
+    ///
+    /// Requests denied by the policy never reach the callback.
+    /// Can be replaced at runtime with `NostrAndroidSignerProxy::set_policy`.
+    pub fn policy(self: Arc<Self>, policy: Policy) -> Self {
+        let mut builder = Arc::unwrap_or_clone(self);
+        builder.policy = policy;
+        builder
+    }
+
+    /// Max number of audit records kept in memory (default: 1000)
+    ///
+    /// When full, the oldest records are dropped. Set to `0` to disable the in-memory audit log.
+    pub fn audit_capacity(self: Arc<Self>, capacity: u32) -> Self {
+        let mut builder = Arc::unwrap_or_clone(self);
+        builder.audit_capacity = capacity;
+        builder
+    }
+
+    /// Path of a JSON-lines file where to append the audit records (default: none)
+    ///
+    /// The file is created if it doesn't exist.
+    pub fn audit_path(self: Arc<Self>, path: String) -> Self {
+        let mut builder = Arc::unwrap_or_clone(self);
+        builder.audit_path = Some(path);
+        builder
+    }
+
+    /// How long to remember the requests rejected by the user (default: disabled)
+    ///
+    /// Signing, encryption and decryption requests with the same method, event kind and counterparty
+    /// of a rejected one are answered with `Rejected`, without prompting the user again,
+    /// until the TTL expires or `NostrAndroidSignerProxy::clear_rejections` is called.
+    pub fn rejection_ttl(self: Arc<Self>, ttl: Duration) -> Self {
+        let mut builder = Arc::unwrap_or_clone(self);
+        builder.rejection_ttl = ttl;
+        builder
+    }
+
+    /// Also listen on `127.0.0.1:<port>` (default: disabled)
+    ///
+    /// Useful to reach the proxy from a desktop while debugging.
+    /// TCP connections can't be filtered by UID/PID, so an auth token is required.
+    pub fn tcp_port(self: Arc<Self>, port: u16) -> Self {
+        let mut builder = Arc::unwrap_or_clone(self);
+        builder.tcp_port = Some(port);
+        builder
+    }
+
+    /// Permission mode of the filesystem socket, i.e. `0o660` (default: according to the umask)
+    ///
+    /// Ignored for abstract sockets. The peer credentials are checked anyway.
+    pub fn socket_mode(self: Arc<Self>, mode: u32) -> Self {
+        let mut builder = Arc::unwrap_or_clone(self);
+        builder.socket_mode = Some(mode);
+        builder
+    }
+}
@@ -0,0 +1,188 @@
+//! Request policy
+//!
+//! Declarative rules, set by the app, that restrict which requests reach the callback.
+
+use std::collections::HashSet;
+
+use nostr::PublicKey;
+use uniffi::{Enum, Record};
+
+use crate::error::AndroidSignerProxyError;
+
+/// Policy method
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Enum)]
+pub enum PolicyMethod {
+    GetPublicKey,
+    Logout,
+    SignEvent,
+    Nip04Encrypt,
+    Nip04Decrypt,
+    Nip44Encrypt,
+    Nip44Decrypt,
+    DecryptZapEvent,
+}
+
+/// Policy action
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Enum)]
+pub enum PolicyAction {
+    Allow,
+    Deny,
+}
+
+/// Policy rule
+///
+/// A rule matches a request if all its non-empty conditions match.
+#[derive(Debug, Clone, Record)]
+pub struct PolicyRule {
+    /// Rule name, reported when a request is denied
+    pub name: String,
+    /// Action to take if the rule matches
+    pub action: PolicyAction,
+    /// Methods matched by the rule (empty: any)
+    pub methods: Vec<PolicyMethod>,
+    /// Event kinds matched by the rule (empty: any)
+    ///
+    /// Requests without an event kind never match a rule with kinds.
+    pub kinds: Vec<u16>,
+    /// Counterparty public keys matched by the rule, hex or bech32 (empty: any)
+    ///
+    /// The counterparty is the other public key of the NIP-04/NIP-44 requests
+    /// and the author of the zap event.
+    /// Requests without a counterparty never match a rule with counterparties.
+    pub counterparties: Vec<String>,
+}
+
+/// Request policy
+///
+/// Rules are evaluated in order: the first matching rule decides.
+/// If no rule matches, the default action is taken.
+#[derive(Debug, Clone, Record)]
+pub struct Policy {
+    pub rules: Vec<PolicyRule>,
+    pub default_action: PolicyAction,
+}
+
+impl Default for Policy {
+    fn default() -> Self {
+        Self {
+            rules: Vec::new(),
+            default_action: PolicyAction::Allow,
+        }
+    }
+}
+
+/// Request to check against the policy
+#[derive(Debug, Clone, Copy)]
+pub(crate) struct PolicyRequest<'a> {
+    pub method: PolicyMethod,
+    pub kind: Option<u16>,
+    pub counterparty: Option<&'a PublicKey>,
+}
+
+impl PolicyRequest<'_> {
+    pub(crate) fn new(method: PolicyMethod) -> Self {
+        Self {
+            method,
+            kind: None,
+            counterparty: None,
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+struct CompiledRule {
+    name: String,
+    action: PolicyAction,
+    methods: HashSet<PolicyMethod>,
+    kinds: HashSet<u16>,
+    counterparties: HashSet<PublicKey>,
+}
+
+impl CompiledRule {
+    fn matches(&self, req: &PolicyRequest) -> bool {
+        if !self.methods.is_empty() && !self.methods.contains(&req.method) {
+            return false;
+        }
+
+        if !self.kinds.is_empty() {
+            match req.kind {
+                Some(kind) if self.kinds.contains(&kind) => {}
+                _ => return false,
+            }
+        }
+
+        if !self.counterparties.is_empty() {
+            match req.counterparty {
+                Some(counterparty) if self.counterparties.contains(counterparty) => {}
+                _ => return false,
+            }
+        }
+
+        true
+    }
+}
+
+/// Policy with the public keys already parsed
+#[derive(Debug, Clone)]
+pub(crate) struct PolicyEngine {
+    rules: Vec<CompiledRule>,
+    default_action: PolicyAction,
+}
+
+impl TryFrom<Policy> for PolicyEngine {
+    type Error = AndroidSignerProxyError;
+
+    fn try_from(policy: Policy) -> Result<Self, Self::Error> {
+        let rules: Vec<CompiledRule> = policy
+            .rules
+            .into_iter()
+            .map(|rule| {
+                let counterparties: HashSet<PublicKey> = rule
+                    .counterparties
+                    .iter()
+                    .map(|pk| {
+                        PublicKey::parse(pk).map_err(|e| {
+                            AndroidSignerProxyError::InvalidArgument(format!(
+                                "Invalid counterparty in policy rule '{}': {e}",
+                                rule.name
+                            ))
+                        })
+                    })
+                    .collect::<Result<_, _>>()?;
+
+                Ok(CompiledRule {
+                    methods: rule.methods.into_iter().collect(),
+                    kinds: rule.kinds.into_iter().collect(),
+                    counterparties,
+                    action: rule.action,
+                    name: rule.name,
+                })
+            })
+            .collect::<Result<_, AndroidSignerProxyError>>()?;
+
+        Ok(Self {
+            rules,
+            default_action: policy.default_action,
+        })
+    }
+}
+
+impl PolicyEngine {
+    /// Check a request against the policy
+    pub(crate) fn check(&self, req: &PolicyRequest) -> Result<(), AndroidSignerProxyError> {
+        let (rule, action): (Option<&str>, PolicyAction) = self
+            .rules
+            .iter()
+            .find(|rule| rule.matches(req))
+            .map(|rule| (Some(rule.name.as_str()), rule.action))
+            .unwrap_or((None, self.default_action));
+
+        match action {
+            PolicyAction::Allow => Ok(()),
+            PolicyAction::Deny => Err(AndroidSignerProxyError::PolicyDenied(
+                rule.map(String::from)
+                    .unwrap_or_else(|| String::from("default")),
+            )),
+        }
+    }
+}
@@ -0,0 +1,89 @@
+//! Rejection memory
+//!
+//! Remembers the requests rejected by the user, to not prompt again for the same request.
+
+use std::collections::HashMap;
+use std::sync::{Mutex, PoisonError};
+use std::time::{Duration, Instant};
+
+use nostr::PublicKey;
+
+use crate::error::AndroidSignerProxyError;
+use crate::policy::{PolicyMethod, PolicyRequest};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+struct RejectionKey {
+    method: PolicyMethod,
+    kind: Option<u16>,
+    counterparty: Option<PublicKey>,
+}
+
+impl From<&PolicyRequest<'_>> for RejectionKey {
+    fn from(req: &PolicyRequest<'_>) -> Self {
+        Self {
+            method: req.method,
+            kind: req.kind,
+            counterparty: req.counterparty.copied(),
+        }
+    }
+}
+
+/// Rejected requests, with the time of the rejection
+#[derive(Debug)]
+pub(crate) struct RejectionMemory {
+    ttl: Duration,
+    rejections: Mutex<HashMap<RejectionKey, Instant>>,
+}
+
+impl RejectionMemory {
+    /// New rejection memory
+    ///
+    /// If the TTL is zero, nothing is remembered.
+    pub(crate) fn new(ttl: Duration) -> Self {
+        Self {
+            ttl,
+            rejections: Mutex::new(HashMap::new()),
+        }
+    }
+
+    /// Check if the request has been rejected recently
+    pub(crate) fn check(&self, req: &PolicyRequest) -> Result<(), AndroidSignerProxyError> {
+        if self.ttl.is_zero() {
+            return Ok(());
+        }
+
+        let mut rejections = self
+            .rejections
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner);
+        rejections.retain(|_, rejected_at| rejected_at.elapsed() < self.ttl);
+
+        if rejections.contains_key(&RejectionKey::from(req)) {
+            return Err(AndroidSignerProxyError::Rejected);
+        }
+
+        Ok(())
+    }
+
+    /// Remember that the user rejected the request
+    pub(crate) fn remember(&self, req: &PolicyRequest) {
+        if self.ttl.is_zero() {
+            return;
+        }
+
+        let mut rejections = self
+            .rejections
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner);
+        rejections.insert(RejectionKey::from(req), Instant::now());
+    }
+
+    /// Forget all the rejections
+    pub(crate) fn clear(&self) {
+        let mut rejections = self
+            .rejections
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner);
+        rejections.clear();
+    }
+}
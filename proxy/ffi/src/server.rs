@@ -1,120 +1,648 @@
-use std::os::unix::net::UnixListener as StdUnixListener;
-use std::sync::Arc;
+use std::ffi::OsString;
+use std::fs::{self, DirBuilder, Metadata, Permissions};
+use std::future::Future;
+use std::io;
+use std::net::Ipv4Addr;
+use std::os::unix::fs::{DirBuilderExt, FileTypeExt, PermissionsExt};
+use std::os::unix::net::{UnixListener as StdUnixListener, UnixStream as StdUnixStream};
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, PoisonError, RwLock};
+use std::time::Duration;
 
+use nostr::{Event, JsonUtil, PublicKey, UnsignedEvent};
 use nostr_android_signer_proto::android_signer_server::{AndroidSigner, AndroidSignerServer};
 use nostr_android_signer_proto::{
-    GetPublicKeyReply, GetPublicKeyRequest, IsExternalSignerInstalledReply,
-    IsExternalSignerInstalledRequest, Nip04DecryptReply, Nip04DecryptRequest, Nip04EncryptReply,
+    AUTH_TOKEN_METADATA_KEY, DecryptZapEventReply, DecryptZapEventRequest, GetPublicKeyReply,
+    GetPublicKeyRequest, IsExternalSignerInstalledReply, IsExternalSignerInstalledRequest,
+    LogoutReply, LogoutRequest, Nip04DecryptReply, Nip04DecryptRequest, Nip04EncryptReply,
     Nip04EncryptRequest, Nip44DecryptReply, Nip44DecryptRequest, Nip44EncryptReply,
-    Nip44EncryptRequest, SignEventReply, SignEventRequest,
+    Nip44EncryptRequest, SignEventReply, SignEventRequest, SignEventsError, SignEventsReply,
+    SignEventsRequest, SignEventsResult, sign_events_result,
 };
-use tokio::net::UnixListener as TokioUnixListener;
-use tokio_stream::wrappers::UnixListenerStream;
+use serde_json::{Map, Value};
+use tokio::net::unix::UCred;
+use tokio::net::{TcpListener, UnixListener as TokioUnixListener, UnixStream as TokioUnixStream};
+use tokio::sync::{Semaphore, watch};
+use tokio_stream::StreamExt;
+use tokio_stream::wrappers::{TcpListenerStream, UnixListenerStream};
 use tonic::transport::Server;
 use tonic::{Request, Response, Status};
 use uds::{UnixListenerExt, UnixSocketAddr};
-use uniffi::Object;
+use uniffi::{Enum, Object, Record};
 
+use crate::audit::{AuditEntry, AuditLog, AuditMethod, AuditRecord, ErrorClass};
+use crate::coalesce::{CoalesceKey, Coalescer};
 use crate::error::AndroidSignerProxyError;
+use crate::options::NostrAndroidSignerProxyOptions;
+use crate::policy::{Policy, PolicyEngine, PolicyMethod, PolicyRequest};
+use crate::rejection::RejectionMemory;
+use crate::{validate, verify};
+
+/// Max number of pending [`NostrAndroidSignerProxyCallback::on_connection_rejected`] calls
+const MAX_PENDING_REJECTION_REPORTS: usize = 4;
+
+/// Shutdown state of the proxy
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum ShutdownState {
+    Running,
+    /// New requests are refused, pending ones can still complete
+    Draining,
+    /// The grace period expired: pending requests are aborted
+    Stopped,
+}
+
+/// Cached public key of the logged-in user
+///
+/// Set by the last `get_public_key` that reached the callback and cleared by `logout`.
+/// Client-side account switches are not reported to the proxy, so they don't change it.
+#[derive(Debug, Default)]
+struct PublicKeyCache {
+    public_key: Option<PublicKey>,
+    /// Incremented at every logout, so a login completing after it doesn't fill the cache again
+    logout_generation: u64,
+}
 
 pub struct SignerAdapter {
     callback: Arc<dyn NostrAndroidSignerProxyCallback>,
+    shutdown: watch::Receiver<ShutdownState>,
+    policy: Arc<RwLock<PolicyEngine>>,
+    audit: Arc<AuditLog>,
+    rejections: Arc<RejectionMemory>,
+    public_key: Arc<RwLock<PublicKeyCache>>,
+    /// In-flight requests, by reply type
+    in_flight_bool: Coalescer<bool>,
+    in_flight_string: Coalescer<String>,
+    in_flight_unit: Coalescer<()>,
+    in_flight_batch: Coalescer<Vec<SignEventResult>>,
+}
+
+impl SignerAdapter {
+    /// Record the outcome of a request in the audit log
+    fn audit<T>(&self, entry: AuditEntry, res: &Result<T, Status>) {
+        self.audit
+            .record(entry.finish(res.as_ref().err().map(ErrorClass::from)));
+    }
+
+    /// Check a request against the policy
+    fn check_policy(&self, req: PolicyRequest) -> Result<(), AndroidSignerProxyError> {
+        let policy = self.policy.read().unwrap_or_else(PoisonError::into_inner);
+        policy.check(&req)
+    }
+
+    /// Forward a request to the callback, remembering if the user rejects it
+    async fn forward<F, T>(&self, req: &PolicyRequest<'_>, future: F) -> Result<T, Status>
+    where
+        F: Future<Output = Result<T, AndroidSignerProxyError>>,
+    {
+        self.guard(async {
+            let res: Result<T, AndroidSignerProxyError> = future.await;
+            if let Err(AndroidSignerProxyError::Rejected) = &res {
+                self.rejections.remember(req);
+            }
+            res
+        })
+        .await
+    }
+
+    /// Wait for a callback, unless the proxy is stopped in the meanwhile
+    async fn guard<F, T>(&self, future: F) -> Result<T, Status>
+    where
+        F: Future<Output = Result<T, AndroidSignerProxyError>>,
+    {
+        let mut shutdown: watch::Receiver<ShutdownState> = self.shutdown.clone();
+
+        tokio::select! {
+            res = future => Ok(res?),
+            _ = shutdown.wait_for(|state| *state == ShutdownState::Stopped) => {
+                Err(Status::unavailable("Proxy shut down"))
+            }
+        }
+    }
 }
 
 #[tonic::async_trait]
 impl AndroidSigner for SignerAdapter {
     async fn is_external_signer_installed(
         &self,
-        _request: Request<IsExternalSignerInstalledRequest>,
+        request: Request<IsExternalSignerInstalledRequest>,
     ) -> Result<Response<IsExternalSignerInstalledReply>, Status> {
-        let res: bool = self.callback.is_external_signer_installed().await?;
-        Ok(Response::new(IsExternalSignerInstalledReply {
-            installed: res,
-        }))
+        let entry = AuditEntry::new(AuditMethod::IsExternalSignerInstalled);
+        let key: CoalesceKey = CoalesceKey::new("is_external_signer_installed", request.get_ref());
+        let res: Result<Response<IsExternalSignerInstalledReply>, Status> = async {
+            let res: bool = self
+                .guard(self.in_flight_bool.run(key, {
+                    let callback = self.callback.clone();
+                    async move { callback.is_external_signer_installed().await }
+                }))
+                .await?;
+            Ok(Response::new(IsExternalSignerInstalledReply {
+                installed: res,
+            }))
+        }
+        .await;
+        self.audit(entry, &res);
+        res
     }
 
     async fn get_public_key(
         &self,
-        _request: Request<GetPublicKeyRequest>,
+        request: Request<GetPublicKeyRequest>,
     ) -> Result<Response<GetPublicKeyReply>, Status> {
-        let public_key: String = self.callback.get_public_key().await?;
-        Ok(Response::new(GetPublicKeyReply { public_key }))
+        let entry = AuditEntry::new(AuditMethod::GetPublicKey);
+        let res: Result<Response<GetPublicKeyReply>, Status> = async {
+            let req: GetPublicKeyRequest = request.into_inner();
+            let key: CoalesceKey = CoalesceKey::new("get_public_key", &req);
+            let permissions: Vec<Permission> = req
+                .permissions
+                .into_iter()
+                .map(|p| {
+                    let kind: Option<u16> = p
+                        .kind
+                        .map(u16::try_from)
+                        .transpose()
+                        .map_err(|_| Status::invalid_argument("Invalid event kind"))?;
+                    Ok(Permission {
+                        method: p.method,
+                        kind,
+                    })
+                })
+                .collect::<Result<_, Status>>()?;
+            self.check_policy(PolicyRequest::new(PolicyMethod::GetPublicKey))?;
+
+            // Serve from the cache, unless a refresh is requested or there are permissions to ask for
+            let logout_generation: u64 = {
+                let cache = self
+                    .public_key
+                    .read()
+                    .unwrap_or_else(PoisonError::into_inner);
+                if !req.force_refresh && permissions.is_empty() {
+                    if let Some(public_key) = cache.public_key {
+                        return Ok(Response::new(GetPublicKeyReply {
+                            public_key: public_key.to_hex(),
+                        }));
+                    }
+                }
+                cache.logout_generation
+            };
+
+            let force_refresh: bool = req.force_refresh;
+            let public_key: String = self
+                .guard(self.in_flight_string.run(key, {
+                    let callback = self.callback.clone();
+                    async move { callback.get_public_key(permissions, force_refresh).await }
+                }))
+                .await?;
+            let public_key: PublicKey = verify::public_key(&public_key)?;
+
+            // Cache the logged-in public key, unless logged out in the meanwhile
+            {
+                let mut cache = self
+                    .public_key
+                    .write()
+                    .unwrap_or_else(PoisonError::into_inner);
+                if cache.logout_generation == logout_generation {
+                    cache.public_key = Some(public_key);
+                }
+            }
+
+            Ok(Response::new(GetPublicKeyReply {
+                public_key: public_key.to_hex(),
+            }))
+        }
+        .await;
+        self.audit(entry, &res);
+        res
+    }
+
+    async fn logout(
+        &self,
+        request: Request<LogoutRequest>,
+    ) -> Result<Response<LogoutReply>, Status> {
+        let entry = AuditEntry::new(AuditMethod::Logout);
+        let key: CoalesceKey = CoalesceKey::new("logout", request.get_ref());
+        let res: Result<Response<LogoutReply>, Status> = async {
+            self.check_policy(PolicyRequest::new(PolicyMethod::Logout))?;
+
+            // Clear the cached public key, even if the signer fails to log out
+            {
+                let mut cache = self
+                    .public_key
+                    .write()
+                    .unwrap_or_else(PoisonError::into_inner);
+                cache.public_key = None;
+                cache.logout_generation += 1;
+            }
+
+            self.guard(self.in_flight_unit.run(key, {
+                let callback = self.callback.clone();
+                async move { callback.logout().await }
+            }))
+            .await?;
+            Ok(Response::new(LogoutReply {}))
+        }
+        .await;
+        self.audit(entry, &res);
+        res
     }
 
     async fn sign_event(
         &self,
         request: Request<SignEventRequest>,
     ) -> Result<Response<SignEventReply>, Status> {
-        let req: SignEventRequest = request.into_inner();
-        let event: String = self
-            .callback
-            .sign_event(req.unsigned_event, req.current_user_public_key)
-            .await?;
-        Ok(Response::new(SignEventReply { event }))
+        let mut entry = AuditEntry::new(AuditMethod::SignEvent);
+        let res: Result<Response<SignEventReply>, Status> = async {
+            let req: SignEventRequest = request.into_inner();
+            let key: CoalesceKey = CoalesceKey::new("sign_event", &req);
+            let unsigned: UnsignedEvent =
+                validate::unsigned_event(&req.unsigned_event, &req.current_user_public_key)?;
+            entry.kind = Some(unsigned.kind.as_u16());
+            entry.event_id = Some(unsigned.clone().id());
+            let policy_req = PolicyRequest {
+                kind: Some(unsigned.kind.as_u16()),
+                ..PolicyRequest::new(PolicyMethod::SignEvent)
+            };
+            self.check_policy(policy_req)?;
+            self.rejections.check(&policy_req)?;
+            let event: String = self
+                .forward(
+                    &policy_req,
+                    self.in_flight_string.run(key, {
+                        let callback = self.callback.clone();
+                        async move {
+                            callback
+                                .sign_event(req.unsigned_event, req.current_user_public_key)
+                                .await
+                        }
+                    }),
+                )
+                .await?;
+            let event: Event = verify::signed_event(&unsigned, &event)?;
+            Ok(Response::new(SignEventReply {
+                event: event.as_json(),
+            }))
+        }
+        .await;
+        self.audit(entry, &res);
+        res
+    }
+
+    async fn sign_events(
+        &self,
+        request: Request<SignEventsRequest>,
+    ) -> Result<Response<SignEventsReply>, Status> {
+        let entry = AuditEntry::new(AuditMethod::SignEvents);
+        let mut records: Vec<AuditRecord> = Vec::new();
+        let res: Result<Response<SignEventsReply>, Status> = async {
+            let req: SignEventsRequest = request.into_inner();
+            let key: CoalesceKey = CoalesceKey::new("sign_events", &req);
+
+            // Refuse the whole batch if any event is invalid, before prompting the user
+            let unsigned: Vec<UnsignedEvent> = req
+                .events
+                .iter()
+                .enumerate()
+                .map(|(index, e)| {
+                    validate::unsigned_event(&e.unsigned_event, &e.current_user_public_key)
+                        .map_err(|e| Status::invalid_argument(format!("Event #{index}: {e}")))
+                })
+                .collect::<Result<_, Status>>()?;
+
+            // Check the policy for each event: only the allowed ones are forwarded to the signer
+            let denied: Vec<Option<AndroidSignerProxyError>> = unsigned
+                .iter()
+                .map(|unsigned| {
+                    let policy_req = PolicyRequest {
+                        kind: Some(unsigned.kind.as_u16()),
+                        ..PolicyRequest::new(PolicyMethod::SignEvent)
+                    };
+                    self.check_policy(policy_req)
+                        .and_then(|()| self.rejections.check(&policy_req))
+                        .err()
+                })
+                .collect();
+
+            // Remembered for each forwarded event if the user rejects the whole batch
+            let forwarded: Vec<PolicyRequest> = unsigned
+                .iter()
+                .zip(denied.iter())
+                .filter(|(_, denied)| denied.is_none())
+                .map(|(unsigned, _)| PolicyRequest {
+                    kind: Some(unsigned.kind.as_u16()),
+                    ..PolicyRequest::new(PolicyMethod::SignEvent)
+                })
+                .collect();
+
+            let events: Vec<SignEventArgs> = req
+                .events
+                .into_iter()
+                .zip(denied.iter())
+                .filter(|(_, denied)| denied.is_none())
+                .map(|(e, _)| SignEventArgs {
+                    unsigned: e.unsigned_event,
+                    current_user_public_key: e.current_user_public_key,
+                })
+                .collect();
+            let len: usize = events.len();
+
+            let results: Vec<SignEventResult> = if events.is_empty() {
+                Vec::new()
+            } else {
+                self.guard(async {
+                    let res: Result<Vec<SignEventResult>, AndroidSignerProxyError> = self
+                        .in_flight_batch
+                        .run(key, {
+                            let callback = self.callback.clone();
+                            async move { callback.sign_events(events).await }
+                        })
+                        .await;
+                    if let Err(AndroidSignerProxyError::Rejected) = &res {
+                        for req in forwarded.iter() {
+                            self.rejections.remember(req);
+                        }
+                    }
+                    res
+                })
+                .await?
+            };
+
+            // Results are matched by position, so they must be exactly one per event
+            if results.len() != len {
+                return Err(Status::internal(format!(
+                    "Expected {len} results, got {}",
+                    results.len()
+                )));
+            }
+
+            let mut results = results.into_iter();
+            let results: Vec<SignEventsResult> = unsigned
+                .iter()
+                .zip(denied)
+                .map(|(unsigned, denied)| {
+                    let result: Result<Event, AndroidSignerProxyError> = match denied {
+                        Some(error) => Err(error),
+                        None => match results.next() {
+                            Some(SignEventResult::Success { event }) => {
+                                verify::signed_event(unsigned, &event)
+                            }
+                            Some(SignEventResult::Failure { error }) => {
+                                if let AndroidSignerProxyError::Rejected = error {
+                                    self.rejections.remember(&PolicyRequest {
+                                        kind: Some(unsigned.kind.as_u16()),
+                                        ..PolicyRequest::new(PolicyMethod::SignEvent)
+                                    });
+                                }
+                                Err(error)
+                            }
+                            None => Err(AndroidSignerProxyError::InvalidSignerResponse(
+                                String::from("Missing result"),
+                            )),
+                        },
+                    };
+
+                    // Audit each event of the batch
+                    let mut event_entry: AuditEntry = entry.split();
+                    event_entry.kind = Some(unsigned.kind.as_u16());
+                    event_entry.event_id = Some(unsigned.clone().id());
+                    records.push(event_entry.finish(result.as_ref().err().map(ErrorClass::from)));
+
+                    SignEventsResult {
+                        result: Some(match result {
+                            Ok(event) => sign_events_result::Result::Event(event.as_json()),
+                            Err(error) => sign_events_result::Result::Error(SignEventsError {
+                                code: error.code() as i32,
+                                policy_rule: error.policy_rule().map(String::from),
+                                message: error.to_string(),
+                            }),
+                        }),
+                    }
+                })
+                .collect();
+
+            Ok(Response::new(SignEventsReply { results }))
+        }
+        .await;
+
+        // If the whole batch failed, there is a single record
+        if res.is_ok() {
+            for record in records.into_iter() {
+                self.audit.record(record);
+            }
+        } else {
+            self.audit(entry, &res);
+        }
+
+        res
     }
 
     async fn nip04_encrypt(
         &self,
         request: Request<Nip04EncryptRequest>,
     ) -> Result<Response<Nip04EncryptReply>, Status> {
-        let req: Nip04EncryptRequest = request.into_inner();
-        let ciphertext: String = self
-            .callback
-            .nip04_encrypt(
-                req.current_user_public_key,
-                req.other_public_key,
-                req.plaintext,
-            )
-            .await?;
-        Ok(Response::new(Nip04EncryptReply { ciphertext }))
+        let mut entry = AuditEntry::new(AuditMethod::Nip04Encrypt);
+        let res: Result<Response<Nip04EncryptReply>, Status> = async {
+            let req: Nip04EncryptRequest = request.into_inner();
+            let key: CoalesceKey = CoalesceKey::new("nip04_encrypt", &req);
+            validate::public_key("current user public key", &req.current_user_public_key)?;
+            let other_public_key: PublicKey =
+                validate::public_key("other public key", &req.other_public_key)?;
+            entry.counterparty = Some(other_public_key);
+            let policy_req = PolicyRequest {
+                counterparty: Some(&other_public_key),
+                ..PolicyRequest::new(PolicyMethod::Nip04Encrypt)
+            };
+            self.check_policy(policy_req)?;
+            self.rejections.check(&policy_req)?;
+            let ciphertext: String = self
+                .forward(
+                    &policy_req,
+                    self.in_flight_string.run(key, {
+                        let callback = self.callback.clone();
+                        async move {
+                            callback
+                                .nip04_encrypt(
+                                    req.current_user_public_key,
+                                    req.other_public_key,
+                                    req.plaintext,
+                                )
+                                .await
+                        }
+                    }),
+                )
+                .await?;
+            Ok(Response::new(Nip04EncryptReply { ciphertext }))
+        }
+        .await;
+        self.audit(entry, &res);
+        res
     }
 
     async fn nip04_decrypt(
         &self,
         request: Request<Nip04DecryptRequest>,
     ) -> Result<Response<Nip04DecryptReply>, Status> {
-        let req: Nip04DecryptRequest = request.into_inner();
-        let plaintext: String = self
-            .callback
-            .nip04_encrypt(
-                req.current_user_public_key,
-                req.other_public_key,
-                req.ciphertext,
-            )
-            .await?;
-        Ok(Response::new(Nip04DecryptReply { plaintext }))
+        let mut entry = AuditEntry::new(AuditMethod::Nip04Decrypt);
+        let res: Result<Response<Nip04DecryptReply>, Status> = async {
+            let req: Nip04DecryptRequest = request.into_inner();
+            let key: CoalesceKey = CoalesceKey::new("nip04_decrypt", &req);
+            validate::public_key("current user public key", &req.current_user_public_key)?;
+            let other_public_key: PublicKey =
+                validate::public_key("other public key", &req.other_public_key)?;
+            entry.counterparty = Some(other_public_key);
+            let policy_req = PolicyRequest {
+                counterparty: Some(&other_public_key),
+                ..PolicyRequest::new(PolicyMethod::Nip04Decrypt)
+            };
+            self.check_policy(policy_req)?;
+            self.rejections.check(&policy_req)?;
+            validate::nip04_payload(&req.ciphertext)?;
+            let plaintext: String = self
+                .forward(
+                    &policy_req,
+                    self.in_flight_string.run(key, {
+                        let callback = self.callback.clone();
+                        async move {
+                            callback
+                                .nip04_decrypt(
+                                    req.current_user_public_key,
+                                    req.other_public_key,
+                                    req.ciphertext,
+                                )
+                                .await
+                        }
+                    }),
+                )
+                .await?;
+            Ok(Response::new(Nip04DecryptReply { plaintext }))
+        }
+        .await;
+        self.audit(entry, &res);
+        res
     }
 
     async fn nip44_encrypt(
         &self,
         request: Request<Nip44EncryptRequest>,
     ) -> Result<Response<Nip44EncryptReply>, Status> {
-        let req: Nip44EncryptRequest = request.into_inner();
-        let ciphertext: String = self
-            .callback
-            .nip44_encrypt(
-                req.current_user_public_key,
-                req.other_public_key,
-                req.plaintext,
-            )
-            .await?;
-        Ok(Response::new(Nip44EncryptReply { ciphertext }))
+        let mut entry = AuditEntry::new(AuditMethod::Nip44Encrypt);
+        let res: Result<Response<Nip44EncryptReply>, Status> = async {
+            let req: Nip44EncryptRequest = request.into_inner();
+            let key: CoalesceKey = CoalesceKey::new("nip44_encrypt", &req);
+            validate::public_key("current user public key", &req.current_user_public_key)?;
+            let other_public_key: PublicKey =
+                validate::public_key("other public key", &req.other_public_key)?;
+            entry.counterparty = Some(other_public_key);
+            let policy_req = PolicyRequest {
+                counterparty: Some(&other_public_key),
+                ..PolicyRequest::new(PolicyMethod::Nip44Encrypt)
+            };
+            self.check_policy(policy_req)?;
+            self.rejections.check(&policy_req)?;
+            validate::nip44_plaintext(&req.plaintext)?;
+            let ciphertext: String = self
+                .forward(
+                    &policy_req,
+                    self.in_flight_string.run(key, {
+                        let callback = self.callback.clone();
+                        async move {
+                            callback
+                                .nip44_encrypt(
+                                    req.current_user_public_key,
+                                    req.other_public_key,
+                                    req.plaintext,
+                                )
+                                .await
+                        }
+                    }),
+                )
+                .await?;
+            Ok(Response::new(Nip44EncryptReply { ciphertext }))
+        }
+        .await;
+        self.audit(entry, &res);
+        res
     }
 
     async fn nip44_decrypt(
         &self,
         request: Request<Nip44DecryptRequest>,
     ) -> Result<Response<Nip44DecryptReply>, Status> {
-        let req: Nip44DecryptRequest = request.into_inner();
-        let plaintext: String = self
-            .callback
-            .nip44_encrypt(
-                req.current_user_public_key,
-                req.other_public_key,
-                req.ciphertext,
-            )
-            .await?;
-        Ok(Response::new(Nip44DecryptReply { plaintext }))
+        let mut entry = AuditEntry::new(AuditMethod::Nip44Decrypt);
+        let res: Result<Response<Nip44DecryptReply>, Status> = async {
+            let req: Nip44DecryptRequest = request.into_inner();
+            let key: CoalesceKey = CoalesceKey::new("nip44_decrypt", &req);
+            validate::public_key("current user public key", &req.current_user_public_key)?;
+            let other_public_key: PublicKey =
+                validate::public_key("other public key", &req.other_public_key)?;
+            entry.counterparty = Some(other_public_key);
+            let policy_req = PolicyRequest {
+                counterparty: Some(&other_public_key),
+                ..PolicyRequest::new(PolicyMethod::Nip44Decrypt)
+            };
+            self.check_policy(policy_req)?;
+            self.rejections.check(&policy_req)?;
+            validate::nip44_payload(&req.ciphertext)?;
+            let plaintext: String = self
+                .forward(
+                    &policy_req,
+                    self.in_flight_string.run(key, {
+                        let callback = self.callback.clone();
+                        async move {
+                            callback
+                                .nip44_decrypt(
+                                    req.current_user_public_key,
+                                    req.other_public_key,
+                                    req.ciphertext,
+                                )
+                                .await
+                        }
+                    }),
+                )
+                .await?;
+            Ok(Response::new(Nip44DecryptReply { plaintext }))
+        }
+        .await;
+        self.audit(entry, &res);
+        res
+    }
+
+    async fn decrypt_zap_event(
+        &self,
+        request: Request<DecryptZapEventRequest>,
+    ) -> Result<Response<DecryptZapEventReply>, Status> {
+        let mut entry = AuditEntry::new(AuditMethod::DecryptZapEvent);
+        let res: Result<Response<DecryptZapEventReply>, Status> = async {
+            let req: DecryptZapEventRequest = request.into_inner();
+            let key: CoalesceKey = CoalesceKey::new("decrypt_zap_event", &req);
+            validate::public_key("current user public key", &req.current_user_public_key)?;
+            let zap: Event = validate::event(&req.event)?;
+            entry.kind = Some(zap.kind.as_u16());
+            entry.event_id = Some(zap.id);
+            entry.counterparty = Some(zap.pubkey);
+            let policy_req = PolicyRequest {
+                kind: Some(zap.kind.as_u16()),
+                counterparty: Some(&zap.pubkey),
+                ..PolicyRequest::new(PolicyMethod::DecryptZapEvent)
+            };
+            self.check_policy(policy_req)?;
+            self.rejections.check(&policy_req)?;
+            let event: String = self
+                .forward(
+                    &policy_req,
+                    self.in_flight_string.run(key, {
+                        let callback = self.callback.clone();
+                        async move {
+                            callback
+                                .decrypt_zap_event(req.event, req.current_user_public_key)
+                                .await
+                        }
+                    }),
+                )
+                .await?;
+            Ok(Response::new(DecryptZapEventReply { event }))
+        }
+        .await;
+        self.audit(entry, &res);
+        res
     }
 }
 
@@ -123,6 +651,12 @@ pub struct NostrAndroidSignerProxy {
     /// UNIX socket address
     socket_addr: UnixSocketAddr,
     callback: Arc<dyn NostrAndroidSignerProxyCallback>,
+    shutdown: Arc<watch::Sender<ShutdownState>>,
+    policy: Arc<RwLock<PolicyEngine>>,
+    audit: Arc<AuditLog>,
+    rejections: Arc<RejectionMemory>,
+    public_key: Arc<RwLock<PublicKeyCache>>,
+    opts: NostrAndroidSignerProxyOptions,
 }
 
 #[uniffi::export(async_runtime = "tokio")]
@@ -131,36 +665,321 @@ impl NostrAndroidSignerProxy {
     pub fn new(
         unique_name: &str,
         callback: Arc<dyn NostrAndroidSignerProxyCallback>,
+    ) -> Result<Self, AndroidSignerProxyError> {
+        Self::with_opts(
+            unique_name,
+            callback,
+            Arc::new(NostrAndroidSignerProxyOptions::default()),
+        )
+    }
+
+    #[uniffi::constructor]
+    pub fn with_opts(
+        unique_name: &str,
+        callback: Arc<dyn NostrAndroidSignerProxyCallback>,
+        opts: Arc<NostrAndroidSignerProxyOptions>,
     ) -> Result<Self, AndroidSignerProxyError> {
         let name: String = format!("nip55_proxy_{unique_name}");
+        let socket_addr: UnixSocketAddr = UnixSocketAddr::from_abstract(name.as_bytes())?;
+        Self::with_socket_addr(socket_addr, callback, Arc::unwrap_or_clone(opts))
+    }
 
-        Ok(Self {
-            socket_addr: UnixSocketAddr::from_abstract(name.as_bytes())?,
-            callback,
-        })
+    /// Listen on a filesystem UNIX socket, instead of the abstract one
+    ///
+    /// A socket left at the path by a proxy that didn't shut down cleanly is replaced.
+    /// The socket is removed when the proxy stops.
+    #[uniffi::constructor]
+    pub fn with_path(
+        path: &str,
+        callback: Arc<dyn NostrAndroidSignerProxyCallback>,
+        opts: Arc<NostrAndroidSignerProxyOptions>,
+    ) -> Result<Self, AndroidSignerProxyError> {
+        let socket_addr: UnixSocketAddr = UnixSocketAddr::from_path(path)?;
+        Self::with_socket_addr(socket_addr, callback, Arc::unwrap_or_clone(opts))
     }
 
     /// Run the proxy
+    ///
+    /// Returns after [`NostrAndroidSignerProxy::shutdown`], once all the connections are closed.
     pub async fn run(&self) -> Result<(), AndroidSignerProxyError> {
         let signer = SignerAdapter {
             callback: self.callback.clone(),
+            shutdown: self.shutdown.subscribe(),
+            policy: self.policy.clone(),
+            audit: self.audit.clone(),
+            rejections: self.rejections.clone(),
+            public_key: self.public_key.clone(),
+            in_flight_bool: Coalescer::default(),
+            in_flight_string: Coalescer::default(),
+            in_flight_unit: Coalescer::default(),
+            in_flight_batch: Coalescer::default(),
         };
 
-        let listener: TokioUnixListener = bind_socket(&self.socket_addr)?;
-        let stream: UnixListenerStream = UnixListenerStream::new(listener);
+        // By default, allow only the UID of this process
+        let allowed_uids: Vec<u32> = if self.opts.allowed_uids.is_empty() {
+            vec![current_uid()]
+        } else {
+            self.opts.allowed_uids.clone()
+        };
+        let allowed_pids: Vec<i32> = self.opts.allowed_pids.clone();
 
-        Server::builder()
-            .add_service(AndroidSignerServer::new(signer))
-            .serve_with_incoming(stream)
-            .await?;
+        // Bind only to loopback: the auth token is the only protection
+        let tcp_listener: Option<TcpListener> = match self.opts.tcp_port {
+            Some(port) => Some(TcpListener::bind((Ipv4Addr::LOCALHOST, port)).await?),
+            None => None,
+        };
+
+        let listener: TokioUnixListener = bind_socket(&self.socket_addr, self.opts.socket_mode)?;
+        let callback: Arc<dyn NostrAndroidSignerProxyCallback> = self.callback.clone();
+        let reports: Arc<Semaphore> = Arc::new(Semaphore::new(MAX_PENDING_REJECTION_REPORTS));
+        let stream = UnixListenerStream::new(listener).filter_map(move |res| match res {
+            Ok(stream) => match check_peer(&stream, &allowed_uids, &allowed_pids) {
+                Ok(()) => Some(Ok(stream)),
+                Err(PeerRejected { uid, pid }) => {
+                    // Report without blocking the accept loop. The stream is dropped, closing the connection.
+                    // While too many reports are pending, further rejections aren't reported,
+                    // so a peer flooding the socket can't pile up tasks.
+                    if let Ok(permit) = reports.clone().try_acquire_owned() {
+                        let callback = callback.clone();
+                        tokio::spawn(async move {
+                            callback.on_connection_rejected(uid, pid).await;
+                            drop(permit);
+                        });
+                    }
+                    None
+                }
+            },
+            Err(e) => Some(Err(e)),
+        });
+
+        let auth_token: Option<String> = self.opts.auth_token.clone();
+        let shutdown: watch::Receiver<ShutdownState> = self.shutdown.subscribe();
+        let service = AndroidSignerServer::with_interceptor(signer, move |req: Request<()>| {
+            intercept(req, &shutdown, auth_token.as_deref())
+        });
+
+        // Stop accepting connections when the shutdown is requested,
+        // then abort the pending requests after the grace period.
+        let state: Arc<watch::Sender<ShutdownState>> = self.shutdown.clone();
+        let grace_period: Duration = self.opts.shutdown_grace_period;
+        let signal = async move {
+            shutdown_requested(state.subscribe()).await;
+
+            tokio::spawn(async move {
+                tokio::time::sleep(grace_period).await;
+                state.send_replace(ShutdownState::Stopped);
+            });
+        };
 
+        let uds = Server::builder()
+            .add_service(service.clone())
+            .serve_with_incoming_shutdown(stream, signal);
+
+        let res = match tcp_listener {
+            Some(listener) => {
+                let tcp = Server::builder()
+                    .add_service(service)
+                    .serve_with_incoming_shutdown(
+                        TcpListenerStream::new(listener),
+                        shutdown_requested(self.shutdown.subscribe()),
+                    );
+                tokio::try_join!(uds, tcp).map(|_| ())
+            }
+            None => uds.await,
+        };
+
+        // Remove the filesystem socket, so the next run doesn't find it
+        if let Some(path) = self.socket_addr.as_pathname() {
+            let _ = fs::remove_file(path);
+        }
+
+        Ok(res?)
+    }
+
+    /// Replace the request policy
+    ///
+    /// Applies to the following requests, also if the proxy is already running.
+    pub fn set_policy(&self, policy: Policy) -> Result<(), AndroidSignerProxyError> {
+        let engine: PolicyEngine = PolicyEngine::try_from(policy)?;
+        let mut current = self.policy.write().unwrap_or_else(PoisonError::into_inner);
+        *current = engine;
         Ok(())
     }
+
+    /// Get the audit records, from the oldest
+    ///
+    /// Only the last `NostrAndroidSignerProxyOptions::audit_capacity` records are kept in memory.
+    pub fn audit_records(&self) -> Vec<AuditRecord> {
+        self.audit.records()
+    }
+
+    /// Clear the in-memory audit records
+    ///
+    /// The audit file, if any, is not affected.
+    pub fn clear_audit_records(&self) {
+        self.audit.clear();
+    }
+
+    /// Forget the requests rejected by the user
+    ///
+    /// Call it when the user changes their mind, so the next requests reach the signer again.
+    pub fn clear_rejections(&self) {
+        self.rejections.clear();
+    }
+
+    /// Gracefully shutdown the proxy
+    ///
+    /// New requests are refused with `Unavailable`, while the pending ones can complete
+    /// within the grace period (see `NostrAndroidSignerProxyOptions::shutdown_grace_period`).
+    /// After that, the pending requests are aborted with `Unavailable`.
+    ///
+    /// The proxy can't be restarted after the shutdown.
+    pub fn shutdown(&self) {
+        self.shutdown.send_if_modified(|state| {
+            if *state == ShutdownState::Running {
+                *state = ShutdownState::Draining;
+                true
+            } else {
+                false
+            }
+        });
+    }
+}
+
+impl NostrAndroidSignerProxy {
+    fn with_socket_addr(
+        socket_addr: UnixSocketAddr,
+        callback: Arc<dyn NostrAndroidSignerProxyCallback>,
+        opts: NostrAndroidSignerProxyOptions,
+    ) -> Result<Self, AndroidSignerProxyError> {
+        if opts.tcp_port.is_some() && opts.auth_token.is_none() {
+            return Err(AndroidSignerProxyError::InvalidArgument(String::from(
+                "the TCP listener requires an auth token",
+            )));
+        }
+
+        let policy: PolicyEngine = PolicyEngine::try_from(opts.policy.clone())?;
+        let audit: AuditLog =
+            AuditLog::new(opts.audit_capacity as usize, opts.audit_path.as_deref())?;
+
+        Ok(Self {
+            socket_addr,
+            callback,
+            shutdown: Arc::new(watch::Sender::new(ShutdownState::Running)),
+            policy: Arc::new(RwLock::new(policy)),
+            audit: Arc::new(audit),
+            rejections: Arc::new(RejectionMemory::new(opts.rejection_ttl)),
+            public_key: Arc::new(RwLock::new(PublicKeyCache::default())),
+            opts,
+        })
+    }
+}
+
+/// Wait until the shutdown is requested
+async fn shutdown_requested(mut shutdown: watch::Receiver<ShutdownState>) {
+    let _ = shutdown
+        .wait_for(|state| *state != ShutdownState::Running)
+        .await;
+}
+
+/// Check a request before handling it
+///
+/// New requests are refused while shutting down.
+pub(crate) fn intercept(
+    req: Request<()>,
+    shutdown: &watch::Receiver<ShutdownState>,
+    auth_token: Option<&str>,
+) -> Result<Request<()>, Status> {
+    if *shutdown.borrow() != ShutdownState::Running {
+        return Err(Status::unavailable("Proxy shutting down"));
+    }
+
+    check_auth_token(&req, auth_token)?;
+    Ok(req)
+}
+
+/// Check the auth token sent by the client, if required
+fn check_auth_token(req: &Request<()>, expected: Option<&str>) -> Result<(), Status> {
+    let Some(expected) = expected else {
+        return Ok(());
+    };
+
+    let token: &[u8] = req
+        .metadata()
+        .get(AUTH_TOKEN_METADATA_KEY)
+        .map(|value| value.as_bytes())
+        .ok_or_else(|| Status::unauthenticated("Missing auth token"))?;
+
+    if constant_time_eq(token, expected.as_bytes()) {
+        Ok(())
+    } else {
+        Err(Status::unauthenticated("Invalid auth token"))
+    }
+}
+
+/// Compare without short-circuiting, to not leak the token through timing
+fn constant_time_eq(a: &[u8], b: &[u8]) -> bool {
+    if a.len() != b.len() {
+        return false;
+    }
+
+    a.iter()
+        .zip(b.iter())
+        .fold(0u8, |acc, (x, y)| acc | (x ^ y))
+        == 0
+}
+
+struct PeerRejected {
+    uid: Option<u32>,
+    pid: Option<i32>,
+}
+
+/// Check the credentials (`SO_PEERCRED`) of the connected peer
+fn check_peer(
+    stream: &TokioUnixStream,
+    allowed_uids: &[u32],
+    allowed_pids: &[i32],
+) -> Result<(), PeerRejected> {
+    let cred: UCred = stream.peer_cred().map_err(|_| PeerRejected {
+        uid: None,
+        pid: None,
+    })?;
+
+    let uid: u32 = cred.uid();
+    let pid: Option<i32> = cred.pid();
+
+    let uid_allowed: bool = allowed_uids.contains(&uid);
+    let pid_allowed: bool =
+        allowed_pids.is_empty() || pid.is_some_and(|pid| allowed_pids.contains(&pid));
+
+    if uid_allowed && pid_allowed {
+        Ok(())
+    } else {
+        Err(PeerRejected {
+            uid: Some(uid),
+            pid,
+        })
+    }
+}
+
+/// Get the real UID of the current process
+fn current_uid() -> u32 {
+    rustix::process::getuid().as_raw()
 }
 
-fn bind_socket(socket_addr: &UnixSocketAddr) -> Result<TokioUnixListener, AndroidSignerProxyError> {
-    // Bind abstract socket
-    let listener = StdUnixListener::bind_unix_addr(socket_addr)?;
+fn bind_socket(
+    socket_addr: &UnixSocketAddr,
+    mode: Option<u32>,
+) -> Result<TokioUnixListener, AndroidSignerProxyError> {
+    if let Some(path) = socket_addr.as_pathname() {
+        remove_stale_socket(path)?;
+    }
+
+    // Bind socket
+    let listener: StdUnixListener = match (socket_addr.as_pathname(), mode) {
+        (Some(path), Some(mode)) => bind_socket_with_mode(path, mode)?,
+        _ => StdUnixListener::bind_unix_addr(socket_addr)?,
+    };
 
     // Moves the socket into nonblocking mode
     listener.set_nonblocking(true)?;
@@ -169,12 +988,140 @@ fn bind_socket(socket_addr: &UnixSocketAddr) -> Result<TokioUnixListener, Androi
     Ok(TokioUnixListener::from_std(listener)?)
 }
 
+/// Bind a filesystem socket with the given permissions
+///
+/// The socket is bound in a private directory and moved to its path only after the permissions are set,
+/// so it's never reachable with the permissions derived from the umask.
+fn bind_socket_with_mode(
+    path: &Path,
+    mode: u32,
+) -> Result<StdUnixListener, AndroidSignerProxyError> {
+    let file_name = path.file_name().ok_or_else(|| {
+        io::Error::new(
+            io::ErrorKind::InvalidInput,
+            format!("{} is not a file path", path.display()),
+        )
+    })?;
+
+    // Keep the name short: socket paths have a small max length
+    let mut dir_name: OsString = OsString::from(".");
+    dir_name.push(file_name);
+    dir_name.push(format!(".{}", std::process::id()));
+    let dir: PathBuf = path.with_file_name(dir_name);
+    let tmp: PathBuf = dir.join("s");
+
+    DirBuilder::new().mode(0o700).create(&dir)?;
+
+    let res = (|| {
+        let listener = StdUnixListener::bind(&tmp)?;
+        fs::set_permissions(&tmp, Permissions::from_mode(mode))?;
+        fs::rename(&tmp, path)?;
+        Ok(listener)
+    })();
+
+    // Clean up, removing the socket too if it hasn't been moved
+    let _ = fs::remove_file(&tmp);
+    let _ = fs::remove_dir(&dir);
+
+    res
+}
+
+/// Remove the filesystem socket left by a proxy that didn't shut down cleanly
+///
+/// Fails if another proxy is listening on it. Files that aren't sockets are never removed.
+fn remove_stale_socket(path: &Path) -> Result<(), AndroidSignerProxyError> {
+    let metadata: Metadata = match fs::symlink_metadata(path) {
+        Ok(metadata) => metadata,
+        Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(()),
+        Err(e) => return Err(e.into()),
+    };
+
+    if !metadata.file_type().is_socket() {
+        return Err(io::Error::new(
+            io::ErrorKind::AlreadyExists,
+            format!("{} exists and is not a socket", path.display()),
+        )
+        .into());
+    }
+
+    match StdUnixStream::connect(path) {
+        Ok(..) => Err(io::Error::new(
+            io::ErrorKind::AddrInUse,
+            format!("{} is in use by another proxy", path.display()),
+        )
+        .into()),
+        Err(e) if e.kind() == io::ErrorKind::ConnectionRefused => Ok(fs::remove_file(path)?),
+        Err(e) => Err(e.into()),
+    }
+}
+
+/// NIP-55 permission to pre-approve during login
+#[derive(Record)]
+pub struct Permission {
+    /// The NIP-55 method (i.e., `sign_event`, `nip44_decrypt`)
+    pub method: String,
+    /// The event kind
+    pub kind: Option<u16>,
+}
+
+/// Build the NIP-55 `permissions` JSON array of the login intent (i.e., `[{"type":"sign_event","kind":1}]`)
+///
+/// Returns `None` if there are no permissions.
+#[uniffi::export]
+pub fn permissions_to_json(permissions: Vec<Permission>) -> Option<String> {
+    if permissions.is_empty() {
+        return None;
+    }
+
+    let array: Vec<Value> = permissions
+        .into_iter()
+        .map(|permission| {
+            let mut obj: Map<String, Value> = Map::new();
+            obj.insert(String::from("type"), Value::from(permission.method));
+            if let Some(kind) = permission.kind {
+                obj.insert(String::from("kind"), Value::from(kind));
+            }
+            Value::Object(obj)
+        })
+        .collect();
+    Some(Value::Array(array).to_string())
+}
+
+/// Unsigned event to sign
+#[derive(Record)]
+pub struct SignEventArgs {
+    /// The unsigned event JSON
+    pub unsigned: String,
+    /// Current user public key
+    pub current_user_public_key: String,
+}
+
+/// Result of a single event signing
+#[derive(Clone, Enum)]
+pub enum SignEventResult {
+    /// The signed event JSON
+    Success { event: String },
+    /// The signing failed
+    Failure { error: AndroidSignerProxyError },
+}
+
 #[uniffi::export(with_foreign)]
 #[async_trait::async_trait]
 pub trait NostrAndroidSignerProxyCallback: Send + Sync {
     async fn is_external_signer_installed(&self) -> Result<bool, AndroidSignerProxyError>;
 
-    async fn get_public_key(&self) -> Result<String, AndroidSignerProxyError>;
+    /// Get the public key of the current user
+    ///
+    /// `force_refresh` is set for the explicit logins (i.e., to pick another account):
+    /// the signer must be shown to the user, instead of answering silently.
+    async fn get_public_key(
+        &self,
+        permissions: Vec<Permission>,
+        force_refresh: bool,
+    ) -> Result<String, AndroidSignerProxyError>;
+
+    /// Clear the session (i.e., the signer package name)
+    async fn logout(&self) -> Result<(), AndroidSignerProxyError>;
 
     async fn sign_event(
         &self,
@@ -182,6 +1129,18 @@ pub trait NostrAndroidSignerProxyCallback: Send + Sync {
         current_user_public_key: String,
     ) -> Result<String, AndroidSignerProxyError>;
 
+    /// Sign multiple events
+    ///
+    /// Must return a result for each event, in the same order.
+    ///
+    /// Signer apps have no multi-event intent, so adapters may prompt the user once per event.
+    /// Returning [`AndroidSignerProxyError::Rejected`] rejects the whole batch:
+    /// the proxy remembers the rejection for the kind of every event of the batch.
+    async fn sign_events(
+        &self,
+        events: Vec<SignEventArgs>,
+    ) -> Result<Vec<SignEventResult>, AndroidSignerProxyError>;
+
     async fn nip04_encrypt(
         &self,
         current_user_public_key: String,
@@ -209,4 +1168,16 @@ pub trait NostrAndroidSignerProxyCallback: Send + Sync {
         other_user_public_key: String,
         ciphertext: String,
     ) -> Result<String, AndroidSignerProxyError>;
+
+    async fn decrypt_zap_event(
+        &self,
+        event: String,
+        current_user_public_key: String,
+    ) -> Result<String, AndroidSignerProxyError>;
+
+    /// A connection has been refused because the peer is not allowed
+    ///
+    /// `uid` and `pid` are `None` if the peer credentials can't be read.
+    /// Rejections are not reported while a few reports are still pending.
+    async fn on_connection_rejected(&self, uid: Option<u32>, pid: Option<i32>);
 }
@@ -0,0 +1,1583 @@
+//! End-to-end tests
+//!
+//! The real `AndroidSigner` client talks to the proxy, which forwards to a callback backed by [`Keys`].
+
+use std::fs;
+use std::net::{Ipv4Addr, SocketAddr};
+use std::os::unix::fs::PermissionsExt;
+use std::os::unix::net::{UnixListener, UnixStream};
+use std::path::PathBuf;
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+use base64::Engine;
+use base64::engine::general_purpose;
+use nostr::nips::{nip04, nip44, nip57};
+use nostr::prelude::*;
+use nostr_android_signer::prelude::{
+    AndroidSigner, AndroidSignerOptions, Error as AndroidSignerError, Method,
+    Permission as SignerPermission,
+};
+use nostr_android_signer_proto::android_signer_client::AndroidSignerClient;
+use nostr_android_signer_proto::{
+    AUTH_TOKEN_METADATA_KEY, GetPublicKeyRequest, Nip04DecryptRequest, Nip44DecryptRequest,
+    Nip44EncryptRequest, SignEventRequest,
+};
+use tokio::sync::watch;
+use tokio::task::JoinHandle;
+use tonic::Code;
+use tonic::transport::{Channel, Endpoint};
+use uds::{UnixSocketAddr, UnixStreamExt};
+
+use crate::audit::{AuditMethod, AuditOutcome, AuditRecord};
+use crate::error::AndroidSignerProxyError;
+use crate::options::NostrAndroidSignerProxyOptions;
+use crate::policy::{Policy, PolicyAction, PolicyMethod, PolicyRule};
+use crate::server::{
+    NostrAndroidSignerProxy, NostrAndroidSignerProxyCallback, Permission, ShutdownState,
+    SignEventArgs, SignEventResult, intercept, permissions_to_json,
+};
+
+static SOCKET_COUNTER: AtomicUsize = AtomicUsize::new(0);
+
+/// Random unique name, so tests can run in parallel
+fn unique_name() -> String {
+    let n: usize = SOCKET_COUNTER.fetch_add(1, Ordering::SeqCst);
+    let nonce: String = Keys::generate().public_key().to_hex();
+    format!("test_{}_{n}_{}", std::process::id(), &nonce[..8])
+}
+
+/// Callback that handles the requests with local keys, recording the called methods
+struct KeysCallback {
+    keys: Keys,
+    calls: Mutex<Vec<&'static str>>,
+    reject: AtomicBool,
+    /// Never reply, like a signer waiting for the user
+    hang: AtomicBool,
+    /// Number of callback invocations dropped before completing
+    cancelled: Arc<AtomicUsize>,
+    /// Reported peers of the rejected connections
+    rejected_connections: Mutex<Vec<(Option<u32>, Option<i32>)>>,
+    /// Reply with invalid values, like a buggy or malicious signer app
+    misbehavior: Mutex<Option<Misbehavior>>,
+    /// `permissions` extra of the last login intent
+    permissions: Mutex<Option<String>>,
+    /// `force_refresh` of the `get_public_key` calls
+    logins: Mutex<Vec<bool>>,
+    /// Delay the `get_public_key` replies, like a user picking an account
+    login_delay: Mutex<Duration>,
+}
+
+/// Invalid replies of the callback
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Misbehavior {
+    /// Sign a different content
+    Tampered,
+    /// Sign with other keys
+    WrongKeys,
+    /// Reply with an invalid public key
+    InvalidPublicKey,
+}
+
+/// Count the callback invocations that are dropped while hanging
+struct CancelGuard(Arc<AtomicUsize>);
+
+impl Drop for CancelGuard {
+    fn drop(&mut self) {
+        self.0.fetch_add(1, Ordering::SeqCst);
+    }
+}
+
+impl KeysCallback {
+    fn new(keys: Keys) -> Self {
+        Self {
+            keys,
+            calls: Mutex::new(Vec::new()),
+            reject: AtomicBool::new(false),
+            hang: AtomicBool::new(false),
+            cancelled: Arc::new(AtomicUsize::new(0)),
+            rejected_connections: Mutex::new(Vec::new()),
+            misbehavior: Mutex::new(None),
+            permissions: Mutex::new(None),
+            logins: Mutex::new(Vec::new()),
+            login_delay: Mutex::new(Duration::ZERO),
+        }
+    }
+
+    async fn called(&self, method: &'static str) -> Result<(), AndroidSignerProxyError> {
+        self.calls.lock().unwrap().push(method);
+
+        if self.hang.load(Ordering::SeqCst) {
+            let _guard = CancelGuard(self.cancelled.clone());
+            std::future::pending::<()>().await;
+        }
+
+        if self.reject.load(Ordering::SeqCst) {
+            return Err(AndroidSignerProxyError::Rejected);
+        }
+
+        Ok(())
+    }
+
+    fn misbehave(&self, misbehavior: Misbehavior) {
+        *self.misbehavior.lock().unwrap() = Some(misbehavior);
+    }
+
+    fn misbehavior(&self) -> Option<Misbehavior> {
+        *self.misbehavior.lock().unwrap()
+    }
+
+    fn calls(&self) -> Vec<&'static str> {
+        self.calls.lock().unwrap().clone()
+    }
+
+    /// Check that the current user is the one of the keys
+    fn check_current_user(&self, public_key: &str) -> Result<(), AndroidSignerProxyError> {
+        let public_key = PublicKey::parse(public_key)
+            .map_err(|e| AndroidSignerProxyError::InvalidArgument(e.to_string()))?;
+        if public_key != self.keys.public_key() {
+            return Err(AndroidSignerProxyError::InvalidArgument(String::from(
+                "Unknown current user",
+            )));
+        }
+        Ok(())
+    }
+
+    fn other(&self, public_key: &str) -> Result<PublicKey, AndroidSignerProxyError> {
+        PublicKey::parse(public_key)
+            .map_err(|e| AndroidSignerProxyError::InvalidArgument(e.to_string()))
+    }
+
+    fn sign(
+        &self,
+        unsigned: &str,
+        current_user_public_key: &str,
+    ) -> Result<String, AndroidSignerProxyError> {
+        self.check_current_user(current_user_public_key)?;
+        let mut unsigned = UnsignedEvent::from_json(unsigned)
+            .map_err(|e| AndroidSignerProxyError::InvalidArgument(e.to_string()))?;
+        let mut keys: Keys = self.keys.clone();
+        match self.misbehavior() {
+            Some(Misbehavior::Tampered) => {
+                unsigned.content.push_str(" (tampered)");
+                unsigned.id = None;
+            }
+            Some(Misbehavior::WrongKeys) => {
+                keys = Keys::generate();
+                unsigned.pubkey = keys.public_key();
+                unsigned.id = None;
+            }
+            _ => {}
+        }
+        let event: Event = unsigned
+            .sign_with_keys(&keys)
+            .map_err(|e| AndroidSignerProxyError::Callback(e.to_string()))?;
+        Ok(event.as_json())
+    }
+}
+
+fn callback_error<E>(e: E) -> AndroidSignerProxyError
+where
+    E: std::fmt::Display,
+{
+    AndroidSignerProxyError::Callback(e.to_string())
+}
+
+#[async_trait::async_trait]
+impl NostrAndroidSignerProxyCallback for KeysCallback {
+    async fn is_external_signer_installed(&self) -> Result<bool, AndroidSignerProxyError> {
+        self.called("is_external_signer_installed").await?;
+        Ok(true)
+    }
+
+    async fn get_public_key(
+        &self,
+        permissions: Vec<Permission>,
+        force_refresh: bool,
+    ) -> Result<String, AndroidSignerProxyError> {
+        // Like the Kotlin adapter, which puts them in the login intent
+        *self.permissions.lock().unwrap() = permissions_to_json(permissions);
+        self.logins.lock().unwrap().push(force_refresh);
+        self.called("get_public_key").await?;
+        let delay: Duration = *self.login_delay.lock().unwrap();
+        tokio::time::sleep(delay).await;
+        if self.misbehavior() == Some(Misbehavior::InvalidPublicKey) {
+            return Ok(String::from("npub1invalid"));
+        }
+        // Signer apps may reply with bech32 public keys
+        Ok(self.keys.public_key().to_bech32().map_err(callback_error)?)
+    }
+
+    async fn logout(&self) -> Result<(), AndroidSignerProxyError> {
+        self.called("logout").await
+    }
+
+    async fn sign_event(
+        &self,
+        unsigned: String,
+        current_user_public_key: String,
+    ) -> Result<String, AndroidSignerProxyError> {
+        self.called("sign_event").await?;
+        self.sign(&unsigned, &current_user_public_key)
+    }
+
+    async fn sign_events(
+        &self,
+        events: Vec<SignEventArgs>,
+    ) -> Result<Vec<SignEventResult>, AndroidSignerProxyError> {
+        self.called("sign_events").await?;
+        Ok(events
+            .into_iter()
+            .map(
+                |e| match self.sign(&e.unsigned, &e.current_user_public_key) {
+                    Ok(event) => SignEventResult::Success { event },
+                    Err(error) => SignEventResult::Failure { error },
+                },
+            )
+            .collect())
+    }
+
+    async fn nip04_encrypt(
+        &self,
+        current_user_public_key: String,
+        other_user_public_key: String,
+        plaintext: String,
+    ) -> Result<String, AndroidSignerProxyError> {
+        self.called("nip04_encrypt").await?;
+        self.check_current_user(&current_user_public_key)?;
+        let other: PublicKey = self.other(&other_user_public_key)?;
+        nip04::encrypt(self.keys.secret_key(), &other, plaintext).map_err(callback_error)
+    }
+
+    async fn nip04_decrypt(
+        &self,
+        current_user_public_key: String,
+        other_user_public_key: String,
+        ciphertext: String,
+    ) -> Result<String, AndroidSignerProxyError> {
+        self.called("nip04_decrypt").await?;
+        self.check_current_user(&current_user_public_key)?;
+        let other: PublicKey = self.other(&other_user_public_key)?;
+        nip04::decrypt(self.keys.secret_key(), &other, ciphertext).map_err(callback_error)
+    }
+
+    async fn nip44_encrypt(
+        &self,
+        current_user_public_key: String,
+        other_user_public_key: String,
+        plaintext: String,
+    ) -> Result<String, AndroidSignerProxyError> {
+        self.called("nip44_encrypt").await?;
+        self.check_current_user(&current_user_public_key)?;
+        let other: PublicKey = self.other(&other_user_public_key)?;
+        nip44::encrypt(
+            self.keys.secret_key(),
+            &other,
+            plaintext,
+            nip44::Version::default(),
+        )
+        .map_err(callback_error)
+    }
+
+    async fn nip44_decrypt(
+        &self,
+        current_user_public_key: String,
+        other_user_public_key: String,
+        ciphertext: String,
+    ) -> Result<String, AndroidSignerProxyError> {
+        self.called("nip44_decrypt").await?;
+        self.check_current_user(&current_user_public_key)?;
+        let other: PublicKey = self.other(&other_user_public_key)?;
+        nip44::decrypt(self.keys.secret_key(), &other, ciphertext).map_err(callback_error)
+    }
+
+    async fn decrypt_zap_event(
+        &self,
+        event: String,
+        current_user_public_key: String,
+    ) -> Result<String, AndroidSignerProxyError> {
+        self.called("decrypt_zap_event").await?;
+        self.check_current_user(&current_user_public_key)?;
+        let event: Event = Event::from_json(event)
+            .map_err(|e| AndroidSignerProxyError::InvalidArgument(e.to_string()))?;
+        let zap_request: Event =
+            nip57::decrypt_received_private_zap_message(self.keys.secret_key(), &event)
+                .map_err(callback_error)?;
+        Ok(zap_request.as_json())
+    }
+
+    async fn on_connection_rejected(&self, uid: Option<u32>, pid: Option<i32>) {
+        self.rejected_connections.lock().unwrap().push((uid, pid));
+
+        if self.hang.load(Ordering::SeqCst) {
+            std::future::pending::<()>().await;
+        }
+    }
+}
+
+struct Harness {
+    name: String,
+    signer: AndroidSigner,
+    keys: Keys,
+    callback: Arc<KeysCallback>,
+    proxy: Arc<NostrAndroidSignerProxy>,
+    handle: JoinHandle<Result<(), AndroidSignerProxyError>>,
+}
+
+impl Harness {
+    async fn new() -> Self {
+        Self::with_opts(
+            NostrAndroidSignerProxyOptions::default(),
+            AndroidSignerOptions::default(),
+        )
+        .await
+    }
+
+    async fn with_opts(
+        proxy_opts: NostrAndroidSignerProxyOptions,
+        signer_opts: AndroidSignerOptions,
+    ) -> Self {
+        let name: String = unique_name();
+        let keys: Keys = Keys::generate();
+        let callback: Arc<KeysCallback> = Arc::new(KeysCallback::new(keys.clone()));
+
+        let proxy: Arc<NostrAndroidSignerProxy> = Arc::new(
+            NostrAndroidSignerProxy::with_opts(&name, callback.clone(), Arc::new(proxy_opts))
+                .unwrap(),
+        );
+        let handle = tokio::spawn({
+            let proxy = proxy.clone();
+            async move { proxy.run().await }
+        });
+
+        // The client retries until the proxy is listening
+        let signer: AndroidSigner = AndroidSigner::new(&name, signer_opts).unwrap();
+
+        Self {
+            name,
+            signer,
+            keys,
+            callback,
+            proxy,
+            handle,
+        }
+    }
+
+    async fn login(&self) {
+        assert_eq!(self.signer.login().await.unwrap(), self.keys.public_key());
+    }
+}
+
+impl Drop for Harness {
+    fn drop(&mut self) {
+        self.handle.abort();
+    }
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn test_is_external_signer_installed() {
+    let h = Harness::new().await;
+
+    assert!(h.signer.is_external_signer_installed().await.unwrap());
+    assert_eq!(h.callback.calls(), ["is_external_signer_installed"]);
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn test_get_public_key() {
+    let h = Harness::new().await;
+
+    // The bech32 public key returned by the callback is normalized by the proxy
+    assert_eq!(
+        h.signer.get_public_key().await.unwrap(),
+        h.keys.public_key()
+    );
+    assert_eq!(h.callback.calls(), ["get_public_key"]);
+
+    // Another client is served from the proxy cache
+    let other = AndroidSigner::new(&h.name, AndroidSignerOptions::default()).unwrap();
+    assert_eq!(other.get_public_key().await.unwrap(), h.keys.public_key());
+    assert_eq!(h.callback.calls(), ["get_public_key"]);
+
+    // Login bypasses the cache
+    h.login().await;
+    assert_eq!(h.callback.calls(), ["get_public_key", "get_public_key"]);
+
+    // So do the permission requests, which the signer must see
+    let opts =
+        AndroidSignerOptions::default().permissions([SignerPermission::sign_event(Kind::TextNote)]);
+    let other = AndroidSigner::new(&h.name, opts).unwrap();
+    assert_eq!(other.get_public_key().await.unwrap(), h.keys.public_key());
+    assert_eq!(h.callback.calls(), ["get_public_key"; 3]);
+
+    // Only the explicit login must show the signer
+    assert_eq!(*h.callback.logins.lock().unwrap(), [false, true, false]);
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn test_login_permissions() {
+    let h = Harness::with_opts(
+        NostrAndroidSignerProxyOptions::default(),
+        AndroidSignerOptions::default().permissions([
+            SignerPermission::sign_event(Kind::TextNote),
+            SignerPermission::sign_event(Kind::LongFormTextNote),
+            SignerPermission::new(Method::Nip44Encrypt),
+        ]),
+    )
+    .await;
+    h.login().await;
+
+    let permissions: String = h.callback.permissions.lock().unwrap().clone().unwrap();
+    let permissions: serde_json::Value = serde_json::from_str(&permissions).unwrap();
+    assert_eq!(
+        permissions,
+        serde_json::json!([
+            {"type": "sign_event", "kind": 1},
+            {"type": "sign_event", "kind": 30023},
+            {"type": "nip44_encrypt"},
+        ])
+    );
+
+    // No permissions, no extra
+    h.callback.permissions.lock().unwrap().take();
+    let other = AndroidSigner::new(&h.name, AndroidSignerOptions::default()).unwrap();
+    other.login().await.unwrap();
+    assert_eq!(*h.callback.permissions.lock().unwrap(), None);
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn test_login_permissions_invalid_kind() {
+    let (h, mut client) = raw_client().await;
+    let calls: Vec<&str> = h.callback.calls();
+
+    let res = client
+        .get_public_key(raw_request(GetPublicKeyRequest {
+            permissions: vec![nostr_android_signer_proto::Permission {
+                method: String::from("sign_event"),
+                kind: Some(u32::from(u16::MAX) + 1),
+            }],
+            force_refresh: true,
+        }))
+        .await;
+    assert_eq!(res.unwrap_err().code(), Code::InvalidArgument);
+    assert_eq!(h.callback.calls(), calls);
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn test_switch_account() {
+    let h = Harness::new().await;
+    h.login().await;
+
+    let account: PublicKey = Keys::generate().public_key();
+    h.signer.switch_account(account).await;
+    assert_eq!(h.signer.current_user_public_key().await, Some(account));
+
+    // The proxy still serves the logged-in public key to the other clients
+    let other = AndroidSigner::new(&h.name, AndroidSignerOptions::default()).unwrap();
+    assert_eq!(other.get_public_key().await.unwrap(), h.keys.public_key());
+    assert_eq!(h.callback.calls(), ["get_public_key"]);
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn test_logout() {
+    let h = Harness::new().await;
+    h.login().await;
+
+    h.signer.logout().await.unwrap();
+    assert_eq!(h.signer.current_user_public_key().await, None);
+
+    // The proxy cache has been cleared too
+    assert_eq!(
+        h.signer.get_public_key().await.unwrap(),
+        h.keys.public_key()
+    );
+    assert_eq!(
+        h.callback.calls(),
+        ["get_public_key", "logout", "get_public_key"]
+    );
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn test_logout_during_login() {
+    let h = Harness::new().await;
+    *h.callback.login_delay.lock().unwrap() = Duration::from_millis(300);
+
+    let login = tokio::spawn({
+        let signer = h.signer.clone();
+        async move { signer.login().await }
+    });
+    wait_until(|| h.callback.calls() == ["get_public_key"]).await;
+
+    // Log out while the login is pending
+    let other = AndroidSigner::new(&h.name, AndroidSignerOptions::default()).unwrap();
+    other.logout().await.unwrap();
+    assert_eq!(login.await.unwrap().unwrap(), h.keys.public_key());
+
+    // The logged-out public key hasn't been cached
+    *h.callback.login_delay.lock().unwrap() = Duration::ZERO;
+    let other = AndroidSigner::new(&h.name, AndroidSignerOptions::default()).unwrap();
+    assert_eq!(other.get_public_key().await.unwrap(), h.keys.public_key());
+    assert_eq!(
+        h.callback.calls(),
+        ["get_public_key", "logout", "get_public_key"]
+    );
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn test_sign_event() {
+    let h = Harness::new().await;
+
+    let unsigned: UnsignedEvent = EventBuilder::text_note("hello").build(h.keys.public_key());
+    let event: Event = h.signer.sign_event(unsigned.clone()).await.unwrap();
+
+    event.verify().unwrap();
+    assert_eq!(event.pubkey, h.keys.public_key());
+    assert_eq!(event.content, unsigned.content);
+    assert_eq!(event.kind, unsigned.kind);
+    // The signer interface logs in first, to check the current user
+    assert_eq!(h.callback.calls(), ["get_public_key", "sign_event"]);
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn test_sign_events() {
+    let h = Harness::new().await;
+
+    let unsigned: Vec<UnsignedEvent> = vec![
+        EventBuilder::text_note("first").build(h.keys.public_key()),
+        EventBuilder::text_note("second").build(h.keys.public_key()),
+    ];
+    let results: Vec<Result<Event, AndroidSignerError>> =
+        h.signer.sign_events(unsigned.clone()).await.unwrap();
+
+    assert_eq!(results.len(), 2);
+    for (result, unsigned) in results.into_iter().zip(unsigned) {
+        let event: Event = result.unwrap();
+        event.verify().unwrap();
+        assert_eq!(event.content, unsigned.content);
+    }
+    assert_eq!(h.callback.calls(), ["sign_events"]);
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn test_nip04() {
+    let h = Harness::new().await;
+    h.login().await;
+    let other: Keys = Keys::generate();
+
+    // Encrypt: the other party must be able to decrypt
+    let ciphertext: String = h
+        .signer
+        .nip04_encrypt(&other.public_key(), "to other")
+        .await
+        .unwrap();
+    let plaintext: String =
+        nip04::decrypt(other.secret_key(), &h.keys.public_key(), &ciphertext).unwrap();
+    assert_eq!(plaintext, "to other");
+
+    // Decrypt: a message from the other party
+    let ciphertext: String =
+        nip04::encrypt(other.secret_key(), &h.keys.public_key(), "from other").unwrap();
+    let plaintext: String = h
+        .signer
+        .nip04_decrypt(&other.public_key(), &ciphertext)
+        .await
+        .unwrap();
+    assert_eq!(plaintext, "from other");
+
+    assert_eq!(
+        h.callback.calls(),
+        ["get_public_key", "nip04_encrypt", "nip04_decrypt"]
+    );
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn test_nip44() {
+    let h = Harness::new().await;
+    h.login().await;
+    let other: Keys = Keys::generate();
+
+    // Encrypt: the other party must be able to decrypt
+    let ciphertext: String = h
+        .signer
+        .nip44_encrypt(&other.public_key(), "to other")
+        .await
+        .unwrap();
+    let plaintext: String =
+        nip44::decrypt(other.secret_key(), &h.keys.public_key(), &ciphertext).unwrap();
+    assert_eq!(plaintext, "to other");
+
+    // Decrypt: a message from the other party
+    let ciphertext: String = nip44::encrypt(
+        other.secret_key(),
+        &h.keys.public_key(),
+        "from other",
+        nip44::Version::default(),
+    )
+    .unwrap();
+    let plaintext: String = h
+        .signer
+        .nip44_decrypt(&other.public_key(), &ciphertext)
+        .await
+        .unwrap();
+    assert_eq!(plaintext, "from other");
+
+    assert_eq!(
+        h.callback.calls(),
+        ["get_public_key", "nip44_encrypt", "nip44_decrypt"]
+    );
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn test_decrypt_zap_event() {
+    let h = Harness::new().await;
+    h.login().await;
+    let sender: Keys = Keys::generate();
+
+    let data = nip57::ZapRequestData::new(h.keys.public_key(), [])
+        .message("private message")
+        .amount(21_000);
+    let private_zap: Event = nip57::private_zap_request(data, &sender).unwrap();
+
+    let zap_request: Event = h.signer.decrypt_zap_event(&private_zap).await.unwrap();
+    assert_eq!(zap_request.pubkey, sender.public_key());
+    assert_eq!(zap_request.content, "private message");
+    assert_eq!(h.callback.calls(), ["get_public_key", "decrypt_zap_event"]);
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn test_invalid_public_key() {
+    let h = Harness::new().await;
+    h.callback.misbehave(Misbehavior::InvalidPublicKey);
+
+    let res = h.signer.login().await;
+    assert!(matches!(
+        res,
+        Err(AndroidSignerError::InvalidSignerResponse(..))
+    ));
+    assert!(h.signer.current_user_public_key().await.is_none());
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn test_invalid_signed_event() {
+    let h = Harness::new().await;
+
+    for misbehavior in [Misbehavior::Tampered, Misbehavior::WrongKeys] {
+        h.callback.misbehave(misbehavior);
+
+        let unsigned: UnsignedEvent = EventBuilder::text_note("hello").build(h.keys.public_key());
+        let e = h.signer.sign_event(unsigned.clone()).await.unwrap_err();
+        assert!(
+            e.to_string().contains("Signed event mismatch"),
+            "{misbehavior:?}: {e}"
+        );
+
+        // Each event of a batch is verified
+        let results = h.signer.sign_events(vec![unsigned]).await.unwrap();
+        assert!(
+            matches!(
+                results.as_slice(),
+                [Err(AndroidSignerError::InvalidSignerResponse(..))]
+            ),
+            "{misbehavior:?}: unexpected results {results:?}"
+        );
+    }
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn test_rejected() {
+    let h = Harness::new().await;
+    h.login().await;
+    h.callback.reject.store(true, Ordering::SeqCst);
+
+    let res = h
+        .signer
+        .nip44_encrypt_as(
+            &h.keys.public_key(),
+            &Keys::generate().public_key(),
+            "hello",
+        )
+        .await;
+    assert!(matches!(res, Err(AndroidSignerError::Rejected)));
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn test_batch_rejection_memory() {
+    let h = Harness::with_opts(
+        Arc::new(NostrAndroidSignerProxyOptions::new()).rejection_ttl(Duration::from_secs(60)),
+        AndroidSignerOptions::default(),
+    )
+    .await;
+    h.callback.reject.store(true, Ordering::SeqCst);
+
+    let note: UnsignedEvent = EventBuilder::text_note("hello").build(h.keys.public_key());
+    let reaction: UnsignedEvent = EventBuilder::new(Kind::Reaction, "+").build(h.keys.public_key());
+    let res = h.signer.sign_events(vec![note.clone(), reaction]).await;
+    assert!(matches!(res, Err(AndroidSignerError::Rejected)));
+
+    // Each kind of the rejected batch is remembered
+    h.callback.reject.store(false, Ordering::SeqCst);
+    let results = h.signer.sign_events(vec![note]).await.unwrap();
+    assert!(matches!(
+        results.as_slice(),
+        [Err(AndroidSignerError::Rejected)]
+    ));
+    assert_eq!(h.callback.calls(), ["sign_events"]);
+
+    // Other kinds still reach the callback
+    let metadata: UnsignedEvent =
+        EventBuilder::new(Kind::Metadata, "{}").build(h.keys.public_key());
+    let results = h.signer.sign_events(vec![metadata]).await.unwrap();
+    assert!(matches!(results.as_slice(), [Ok(..)]));
+    assert_eq!(h.callback.calls(), ["sign_events", "sign_events"]);
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn test_rejection_memory() {
+    let h = Harness::with_opts(
+        Arc::new(NostrAndroidSignerProxyOptions::new()).rejection_ttl(Duration::from_secs(60)),
+        AndroidSignerOptions::default(),
+    )
+    .await;
+    h.login().await;
+    h.callback.reject.store(true, Ordering::SeqCst);
+    let other: Keys = Keys::generate();
+
+    for _ in 0..2 {
+        let res = h
+            .signer
+            .nip44_encrypt_as(&h.keys.public_key(), &other.public_key(), "hello")
+            .await;
+        assert!(matches!(res, Err(AndroidSignerError::Rejected)));
+    }
+
+    // The second request didn't reach the callback
+    assert_eq!(h.callback.calls(), ["get_public_key", "nip44_encrypt"]);
+
+    // After clearing, the request reaches the callback again
+    h.proxy.clear_rejections();
+    h.callback.reject.store(false, Ordering::SeqCst);
+    h.signer
+        .nip44_encrypt(&other.public_key(), "hello")
+        .await
+        .unwrap();
+}
+
+/// Poll the condition until it's true, or panic after a few seconds
+async fn wait_until<F>(condition: F)
+where
+    F: Fn() -> bool,
+{
+    tokio::time::timeout(Duration::from_secs(5), async {
+        while !condition() {
+            tokio::time::sleep(Duration::from_millis(10)).await;
+        }
+    })
+    .await
+    .expect("condition not met");
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn test_coalesced_requests_cancelled() {
+    let h = Harness::with_opts(
+        NostrAndroidSignerProxyOptions::default(),
+        AndroidSignerOptions::new().interactive_timeout(Duration::from_millis(500)),
+    )
+    .await;
+    h.login().await;
+    h.callback.hang.store(true, Ordering::SeqCst);
+
+    let other_client = AndroidSigner::new(
+        &h.name,
+        AndroidSignerOptions::new().interactive_timeout(Duration::from_millis(500)),
+    )
+    .unwrap();
+    let current: PublicKey = h.keys.public_key();
+    let other: PublicKey = Keys::generate().public_key();
+
+    let (a, b) = tokio::join!(
+        h.signer.nip44_encrypt_as(&current, &other, "hello"),
+        other_client.nip44_encrypt_as(&current, &other, "hello"),
+    );
+    assert!(matches!(a, Err(AndroidSignerError::Timeout)));
+    assert!(matches!(b, Err(AndroidSignerError::Timeout)));
+
+    // Both clients shared a single callback invocation, cancelled when both gave up
+    assert_eq!(h.callback.calls(), ["get_public_key", "nip44_encrypt"]);
+    wait_until(|| h.callback.cancelled.load(Ordering::SeqCst) == 1).await;
+
+    // A new request invokes the callback again
+    h.callback.hang.store(false, Ordering::SeqCst);
+    h.signer
+        .nip44_encrypt_as(&current, &other, "hello")
+        .await
+        .unwrap();
+    assert_eq!(
+        h.callback.calls(),
+        ["get_public_key", "nip44_encrypt", "nip44_encrypt"]
+    );
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn test_callback_cancelled_on_shutdown() {
+    let grace_period: Duration = Duration::from_millis(500);
+    let mut h = Harness::with_opts(
+        Arc::new(NostrAndroidSignerProxyOptions::new()).shutdown_grace_period(grace_period),
+        AndroidSignerOptions::default(),
+    )
+    .await;
+    h.login().await;
+    h.callback.hang.store(true, Ordering::SeqCst);
+
+    let current_user: PublicKey = h.keys.public_key();
+    let other: PublicKey = Keys::generate().public_key();
+    let request = tokio::spawn({
+        let signer = h.signer.clone();
+        async move {
+            signer
+                .nip44_encrypt_as(&current_user, &other, "hello")
+                .await
+        }
+    });
+    wait_until(|| h.callback.calls().len() == 2).await;
+
+    h.proxy.shutdown();
+    let started_at: Instant = Instant::now();
+
+    // New requests are refused while draining
+    // (by the interceptor or by the connection shutdown, whichever comes first)
+    let res = h
+        .signer
+        .nip44_encrypt_as(&current_user, &other, "hello")
+        .await;
+    assert!(res.is_err(), "{res:?}");
+    assert_eq!(h.callback.calls().len(), 2);
+
+    // The pending callback is cancelled once the grace period has elapsed
+    match request.await.unwrap() {
+        Err(AndroidSignerError::Status(status)) => {
+            assert_eq!(status.code(), Code::Unavailable);
+            assert_eq!(status.message(), "Proxy shut down");
+        }
+        res => panic!("Unexpected result: {res:?}"),
+    }
+    assert!(started_at.elapsed() >= grace_period);
+    wait_until(|| h.callback.cancelled.load(Ordering::SeqCst) == 1).await;
+
+    // The proxy stops right after the grace period
+    tokio::time::timeout(grace_period, &mut h.handle)
+        .await
+        .expect("proxy still running")
+        .unwrap()
+        .unwrap();
+    assert!(started_at.elapsed() < grace_period * 2);
+}
+
+#[test]
+fn test_interceptor_refuses_while_draining() {
+    let (state, shutdown) = watch::channel(ShutdownState::Running);
+    assert!(intercept(tonic::Request::new(()), &shutdown, None).is_ok());
+
+    for draining in [ShutdownState::Draining, ShutdownState::Stopped] {
+        state.send_replace(draining);
+        let status = intercept(tonic::Request::new(()), &shutdown, None).unwrap_err();
+        assert_eq!(status.code(), Code::Unavailable, "{draining:?}");
+        assert_eq!(status.message(), "Proxy shutting down");
+    }
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn test_policy_denied() {
+    let policy = Policy {
+        rules: vec![PolicyRule {
+            name: String::from("no-nip04"),
+            action: PolicyAction::Deny,
+            methods: vec![PolicyMethod::Nip04Encrypt, PolicyMethod::Nip04Decrypt],
+            kinds: Vec::new(),
+            counterparties: Vec::new(),
+        }],
+        default_action: PolicyAction::Allow,
+    };
+    let h = Harness::with_opts(
+        Arc::new(NostrAndroidSignerProxyOptions::new()).policy(policy),
+        AndroidSignerOptions::default(),
+    )
+    .await;
+    h.login().await;
+
+    let res = h
+        .signer
+        .nip04_encrypt_as(
+            &h.keys.public_key(),
+            &Keys::generate().public_key(),
+            "hello",
+        )
+        .await;
+    match res {
+        Err(AndroidSignerError::PolicyDenied(rule)) => assert_eq!(rule, "no-nip04"),
+        res => panic!("Unexpected result: {res:?}"),
+    }
+    assert_eq!(h.callback.calls(), ["get_public_key"]);
+}
+
+/// Policy rule for the tests
+fn rule(name: &str, action: PolicyAction, methods: &[PolicyMethod]) -> PolicyRule {
+    PolicyRule {
+        name: String::from(name),
+        action,
+        methods: methods.to_vec(),
+        kinds: Vec::new(),
+        counterparties: Vec::new(),
+    }
+}
+
+async fn policy_harness(rules: Vec<PolicyRule>, default_action: PolicyAction) -> Harness {
+    let policy = Policy {
+        rules,
+        default_action,
+    };
+    let h = Harness::with_opts(
+        Arc::new(NostrAndroidSignerProxyOptions::new()).policy(policy),
+        AndroidSignerOptions::default(),
+    )
+    .await;
+    h.login().await;
+    h
+}
+
+/// Name of the policy rule that denied the request
+fn denied_by<T>(res: Result<T, AndroidSignerError>) -> String
+where
+    T: std::fmt::Debug,
+{
+    match res {
+        Err(AndroidSignerError::PolicyDenied(rule)) => rule,
+        res => panic!("Unexpected result: {res:?}"),
+    }
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn test_policy_kind_allow_list() {
+    let h = policy_harness(
+        vec![
+            PolicyRule {
+                kinds: vec![0, 1, 3, 7, 1059],
+                ..rule(
+                    "allowed-kinds",
+                    PolicyAction::Allow,
+                    &[PolicyMethod::SignEvent],
+                )
+            },
+            rule(
+                "other-kinds",
+                PolicyAction::Deny,
+                &[PolicyMethod::SignEvent],
+            ),
+        ],
+        PolicyAction::Allow,
+    )
+    .await;
+
+    let unsigned: Vec<UnsignedEvent> = [0, 1, 3, 7, 1059, 4, 30023]
+        .into_iter()
+        .map(|kind| EventBuilder::new(Kind::from(kind), "").build(h.keys.public_key()))
+        .collect();
+    let results = h.signer.sign_events(unsigned.clone()).await.unwrap();
+    let (allowed, denied) = results.split_at(5);
+    assert!(allowed.iter().all(Result::is_ok), "{allowed:?}");
+    for res in denied {
+        match res {
+            Err(AndroidSignerError::PolicyDenied(rule)) => assert_eq!(rule, "other-kinds"),
+            res => panic!("Unexpected result: {res:?}"),
+        }
+    }
+
+    let res = h
+        .signer
+        .sign_events(vec![unsigned[5].clone()])
+        .await
+        .unwrap()
+        .remove(0);
+    assert_eq!(denied_by(res), "other-kinds");
+
+    // Only the allowed kinds reached the callback
+    assert_eq!(h.callback.calls(), ["get_public_key", "sign_events"]);
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn test_policy_contacts() {
+    let contact: Keys = Keys::generate();
+    let stranger: Keys = Keys::generate();
+    let decrypt: [PolicyMethod; 2] = [PolicyMethod::Nip04Decrypt, PolicyMethod::Nip44Decrypt];
+    let h = policy_harness(
+        vec![
+            PolicyRule {
+                counterparties: vec![
+                    contact.public_key().to_bech32().unwrap(),
+                    Keys::generate().public_key().to_hex(),
+                ],
+                ..rule("contacts", PolicyAction::Allow, &decrypt)
+            },
+            rule("strangers", PolicyAction::Deny, &decrypt),
+        ],
+        PolicyAction::Allow,
+    )
+    .await;
+
+    for sender in [&contact, &stranger] {
+        let ciphertext = nip44::encrypt(
+            sender.secret_key(),
+            &h.keys.public_key(),
+            "hello",
+            nip44::Version::default(),
+        )
+        .unwrap();
+        let res = h
+            .signer
+            .nip44_decrypt_as(&h.keys.public_key(), &sender.public_key(), &ciphertext)
+            .await;
+        if sender.public_key() == contact.public_key() {
+            assert_eq!(res.unwrap(), "hello");
+        } else {
+            assert_eq!(denied_by(res), "strangers");
+        }
+    }
+
+    let ciphertext = nip04::encrypt(stranger.secret_key(), &h.keys.public_key(), "hello").unwrap();
+    let res = h
+        .signer
+        .nip04_decrypt_as(&h.keys.public_key(), &stranger.public_key(), &ciphertext)
+        .await;
+    assert_eq!(denied_by(res), "strangers");
+
+    // The rules only match the decryptions
+    h.signer
+        .nip44_encrypt(&stranger.public_key(), "hello")
+        .await
+        .unwrap();
+    assert_eq!(
+        h.callback.calls(),
+        ["get_public_key", "nip44_decrypt", "nip44_encrypt"]
+    );
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn test_policy_default_deny() {
+    let h = policy_harness(
+        vec![rule(
+            "login",
+            PolicyAction::Allow,
+            &[PolicyMethod::GetPublicKey],
+        )],
+        PolicyAction::Deny,
+    )
+    .await;
+
+    let unsigned: UnsignedEvent = EventBuilder::text_note("hello").build(h.keys.public_key());
+    let res = h
+        .signer
+        .sign_events(vec![unsigned])
+        .await
+        .unwrap()
+        .remove(0);
+    assert_eq!(denied_by(res), "default");
+    let res = h
+        .signer
+        .nip44_encrypt_as(
+            &h.keys.public_key(),
+            &Keys::generate().public_key(),
+            "hello",
+        )
+        .await;
+    assert_eq!(denied_by(res), "default");
+    assert_eq!(h.callback.calls(), ["get_public_key"]);
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn test_policy_first_match() {
+    let no_notes = PolicyRule {
+        kinds: vec![1],
+        ..rule("no-notes", PolicyAction::Deny, &[PolicyMethod::SignEvent])
+    };
+    let sign = rule("sign", PolicyAction::Allow, &[PolicyMethod::SignEvent]);
+    let login = rule("login", PolicyAction::Allow, &[PolicyMethod::GetPublicKey]);
+
+    // The first matching rule decides
+    let h = policy_harness(
+        vec![login.clone(), no_notes.clone(), sign.clone()],
+        PolicyAction::Deny,
+    )
+    .await;
+    let note: UnsignedEvent = EventBuilder::text_note("hello").build(h.keys.public_key());
+    let res = h.signer.sign_events(vec![note]).await.unwrap().remove(0);
+    assert_eq!(denied_by(res), "no-notes");
+
+    let h = policy_harness(vec![login, sign, no_notes], PolicyAction::Deny).await;
+    let note: UnsignedEvent = EventBuilder::text_note("hello").build(h.keys.public_key());
+    h.signer.sign_event(note).await.unwrap();
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn test_audit_log() {
+    let path: PathBuf = std::env::temp_dir().join(format!("{}.jsonl", unique_name()));
+    let policy = Policy {
+        rules: vec![PolicyRule {
+            name: String::from("no-nip04"),
+            action: PolicyAction::Deny,
+            methods: vec![PolicyMethod::Nip04Encrypt],
+            kinds: Vec::new(),
+            counterparties: Vec::new(),
+        }],
+        default_action: PolicyAction::Allow,
+    };
+    let opts = Arc::new(NostrAndroidSignerProxyOptions::new())
+        .audit_path(path.to_str().unwrap().to_string());
+    let h = Harness::with_opts(
+        Arc::new(opts).policy(policy),
+        AndroidSignerOptions::default(),
+    )
+    .await;
+    h.login().await;
+    let other: PublicKey = Keys::generate().public_key();
+    let plaintext: &str = "audited plaintext";
+
+    // Success
+    let ciphertext: String = h.signer.nip44_encrypt(&other, plaintext).await.unwrap();
+    let unsigned: UnsignedEvent = EventBuilder::text_note("hello").build(h.keys.public_key());
+    let event: Event = h.signer.sign_event(unsigned).await.unwrap();
+
+    // Rejection
+    h.callback.reject.store(true, Ordering::SeqCst);
+    h.signer
+        .nip44_decrypt(&other, &ciphertext)
+        .await
+        .unwrap_err();
+    h.callback.reject.store(false, Ordering::SeqCst);
+
+    // Policy denial
+    h.signer.nip04_encrypt(&other, plaintext).await.unwrap_err();
+
+    let records: Vec<AuditRecord> = h.proxy.audit_records();
+    let summary: Vec<_> = records
+        .iter()
+        .map(|r| {
+            (
+                r.method,
+                r.kind,
+                r.event_id.clone(),
+                r.counterparty.clone(),
+                r.outcome,
+                r.error_class.clone(),
+            )
+        })
+        .collect();
+    assert_eq!(
+        summary,
+        [
+            (
+                AuditMethod::GetPublicKey,
+                None,
+                None,
+                None,
+                AuditOutcome::Success,
+                None
+            ),
+            (
+                AuditMethod::Nip44Encrypt,
+                None,
+                None,
+                Some(other.to_hex()),
+                AuditOutcome::Success,
+                None
+            ),
+            (
+                AuditMethod::SignEvent,
+                Some(1),
+                Some(event.id.to_hex()),
+                None,
+                AuditOutcome::Success,
+                None
+            ),
+            (
+                AuditMethod::Nip44Decrypt,
+                None,
+                None,
+                Some(other.to_hex()),
+                AuditOutcome::Rejected,
+                Some(String::from("rejected"))
+            ),
+            (
+                AuditMethod::Nip04Encrypt,
+                None,
+                None,
+                Some(other.to_hex()),
+                AuditOutcome::PolicyDenied,
+                Some(String::from("policy_denied"))
+            ),
+        ]
+    );
+
+    // The file is written in the background
+    wait_until(|| fs::read_to_string(&path).is_ok_and(|log| log.lines().count() == records.len()))
+        .await;
+    let log: String = fs::read_to_string(&path).unwrap();
+    let lines: Vec<serde_json::Value> = log
+        .lines()
+        .map(|line| serde_json::from_str(line).unwrap())
+        .collect();
+    let methods: Vec<&str> = lines.iter().filter_map(|l| l["method"].as_str()).collect();
+    assert_eq!(
+        methods,
+        [
+            "get_public_key",
+            "nip44_encrypt",
+            "sign_event",
+            "nip44_decrypt",
+            "nip04_encrypt"
+        ]
+    );
+    let outcomes: Vec<&str> = lines.iter().filter_map(|l| l["outcome"].as_str()).collect();
+    assert_eq!(
+        outcomes,
+        ["success", "success", "success", "rejected", "policy_denied"]
+    );
+    assert_eq!(lines[2]["kind"], 1);
+    assert_eq!(lines[2]["event_id"], event.id.to_hex());
+    assert_eq!(lines[3]["counterparty"], other.to_hex());
+    assert_eq!(lines[4]["error_class"], "policy_denied");
+
+    // Neither the plaintexts nor the ciphertexts are recorded
+    let in_memory: String = format!("{records:?}");
+    for secret in [plaintext, ciphertext.as_str()] {
+        assert!(!in_memory.contains(secret));
+        assert!(!log.contains(secret));
+    }
+
+    fs::remove_file(&path).unwrap();
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn test_auth_token() {
+    let h = Harness::with_opts(
+        Arc::new(NostrAndroidSignerProxyOptions::new()).auth_token(String::from("secret")),
+        AndroidSignerOptions::default().auth_token("wrong"),
+    )
+    .await;
+
+    let res = h.signer.is_external_signer_installed().await;
+    assert!(matches!(res, Err(AndroidSignerError::Unauthenticated)));
+    assert!(h.callback.calls().is_empty());
+}
+
+#[test]
+fn test_auth_token_not_in_debug_output() {
+    let proxy_opts =
+        Arc::new(NostrAndroidSignerProxyOptions::new()).auth_token(String::from("secret"));
+    assert!(!format!("{proxy_opts:?}").contains("secret"));
+
+    let signer_opts = AndroidSignerOptions::new().auth_token("secret");
+    assert!(!format!("{signer_opts:?}").contains("secret"));
+
+    let signer = AndroidSigner::new(&unique_name(), signer_opts).unwrap();
+    assert!(!format!("{signer:?}").contains("secret"));
+}
+
+/// Abstract socket address the proxy listens on
+fn proxy_socket_addr(name: &str) -> UnixSocketAddr {
+    UnixSocketAddr::from_abstract(format!("nip55_proxy_{name}").as_bytes()).unwrap()
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn test_peer_rejected() {
+    let uid: u32 = rustix::process::getuid().as_raw();
+    let h = Harness::with_opts(
+        Arc::new(NostrAndroidSignerProxyOptions::new()).allowed_uids(vec![uid + 1]),
+        AndroidSignerOptions::new().reconnect(false),
+    )
+    .await;
+
+    // Wait for the proxy to listen: this connection is rejected too
+    let addr = proxy_socket_addr(&h.name);
+    wait_until(|| UnixStream::connect_to_unix_addr(&addr).is_ok()).await;
+
+    assert!(h.signer.is_external_signer_installed().await.is_err());
+    wait_until(|| h.callback.rejected_connections.lock().unwrap().len() >= 2).await;
+    for peer in h.callback.rejected_connections.lock().unwrap().iter() {
+        assert_eq!(*peer, (Some(uid), Some(std::process::id() as i32)));
+    }
+
+    // The request never reached the callback
+    assert!(h.callback.calls().is_empty());
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn test_peer_rejected_reports_bounded() {
+    let uid: u32 = rustix::process::getuid().as_raw();
+    let h = Harness::with_opts(
+        Arc::new(NostrAndroidSignerProxyOptions::new()).allowed_uids(vec![uid + 1]),
+        AndroidSignerOptions::new().reconnect(false),
+    )
+    .await;
+
+    // The reports never complete
+    h.callback.hang.store(true, Ordering::SeqCst);
+
+    let addr = proxy_socket_addr(&h.name);
+    for _ in 0..20 {
+        wait_until(|| UnixStream::connect_to_unix_addr(&addr).is_ok()).await;
+    }
+    wait_until(|| h.callback.rejected_connections.lock().unwrap().len() == 4).await;
+
+    // Further rejections are not reported while the others are pending
+    tokio::time::sleep(Duration::from_millis(200)).await;
+    assert_eq!(h.callback.rejected_connections.lock().unwrap().len(), 4);
+}
+
+/// Get a free TCP port on loopback
+fn free_port() -> u16 {
+    let listener = std::net::TcpListener::bind((Ipv4Addr::LOCALHOST, 0)).unwrap();
+    listener.local_addr().unwrap().port()
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn test_tcp_listener() {
+    let port: u16 = free_port();
+    let h = Harness::with_opts(
+        Arc::new(
+            Arc::new(NostrAndroidSignerProxyOptions::new()).auth_token(String::from("secret")),
+        )
+        .tcp_port(port),
+        AndroidSignerOptions::default().auth_token("secret"),
+    )
+    .await;
+    let addr = SocketAddr::from((Ipv4Addr::LOCALHOST, port));
+
+    let signer =
+        AndroidSigner::tcp(addr, AndroidSignerOptions::new().auth_token("secret")).unwrap();
+    assert_eq!(signer.get_public_key().await.unwrap(), h.keys.public_key());
+
+    let signer = AndroidSigner::tcp(addr, AndroidSignerOptions::new()).unwrap();
+    let res = signer.is_external_signer_installed().await;
+    assert!(matches!(res, Err(AndroidSignerError::Unauthenticated)));
+
+    // The UNIX socket is still served
+    assert!(h.signer.is_external_signer_installed().await.unwrap());
+}
+
+/// Harness with a raw gRPC client, to send requests the `AndroidSigner` would never send
+async fn raw_client() -> (Harness, AndroidSignerClient<Channel>) {
+    let port: u16 = free_port();
+    let h = Harness::with_opts(
+        Arc::new(
+            Arc::new(NostrAndroidSignerProxyOptions::new()).auth_token(String::from("secret")),
+        )
+        .tcp_port(port),
+        AndroidSignerOptions::default().auth_token("secret"),
+    )
+    .await;
+
+    // Once logged in, the proxy is listening
+    h.login().await;
+
+    let channel: Channel = Endpoint::from_shared(format!("http://127.0.0.1:{port}"))
+        .unwrap()
+        .connect()
+        .await
+        .unwrap();
+    (h, AndroidSignerClient::new(channel))
+}
+
+fn raw_request<T>(message: T) -> tonic::Request<T> {
+    let mut req = tonic::Request::new(message);
+    req.metadata_mut()
+        .insert(AUTH_TOKEN_METADATA_KEY, "secret".parse().unwrap());
+    req
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn test_invalid_requests() {
+    let (h, mut client) = raw_client().await;
+    let current_user: String = h.keys.public_key().to_hex();
+    let other: String = Keys::generate().public_key().to_hex();
+
+    let mut statuses: Vec<(&str, tonic::Status)> = Vec::new();
+
+    // Invalid current user public key
+    let unsigned: UnsignedEvent = EventBuilder::text_note("hello").build(h.keys.public_key());
+    let res = client
+        .sign_event(raw_request(SignEventRequest {
+            unsigned_event: unsigned.as_json(),
+            current_user_public_key: String::from("invalid"),
+        }))
+        .await;
+    statuses.push(("sign_event: invalid current user", res.unwrap_err()));
+    let res = client
+        .nip44_encrypt(raw_request(Nip44EncryptRequest {
+            current_user_public_key: String::from("npub1invalid"),
+            other_public_key: other.clone(),
+            plaintext: String::from("hello"),
+        }))
+        .await;
+    statuses.push(("nip44_encrypt: invalid current user", res.unwrap_err()));
+
+    // Unsigned event authored by another user
+    let unsigned: UnsignedEvent =
+        EventBuilder::text_note("hello").build(Keys::generate().public_key());
+    let res = client
+        .sign_event(raw_request(SignEventRequest {
+            unsigned_event: unsigned.as_json(),
+            current_user_public_key: current_user.clone(),
+        }))
+        .await;
+    statuses.push(("sign_event: pubkey mismatch", res.unwrap_err()));
+
+    // Malformed NIP-04 payloads
+    for payload in ["no-iv", "!!!?iv=AAAAAAAAAAAAAAAAAAAAAA==", "AAAA?iv=AAAA"] {
+        let res = client
+            .nip04_decrypt(raw_request(Nip04DecryptRequest {
+                current_user_public_key: current_user.clone(),
+                other_public_key: other.clone(),
+                ciphertext: payload.to_string(),
+            }))
+            .await;
+        statuses.push(("nip04_decrypt: malformed payload", res.unwrap_err()));
+    }
+
+    // Malformed NIP-44 payloads
+    for payload in [
+        String::from("#unsupported"),
+        String::from("AAAA"),
+        "!".repeat(200),
+        // Valid size and base64, wrong version
+        general_purpose::STANDARD.encode([1u8; 120]),
+    ] {
+        let res = client
+            .nip44_decrypt(raw_request(Nip44DecryptRequest {
+                current_user_public_key: current_user.clone(),
+                other_public_key: other.clone(),
+                ciphertext: payload,
+            }))
+            .await;
+        statuses.push(("nip44_decrypt: malformed payload", res.unwrap_err()));
+    }
+
+    for (case, status) in statuses {
+        assert_eq!(status.code(), Code::InvalidArgument, "{case}: {status:?}");
+        assert!(matches!(
+            AndroidSignerError::from(status),
+            AndroidSignerError::InvalidArgument(..)
+        ));
+    }
+
+    // The user has never been prompted
+    assert_eq!(h.callback.calls(), ["get_public_key"]);
+}
+
+#[test]
+fn test_tcp_listener_requires_auth_token() {
+    let callback = Arc::new(KeysCallback::new(Keys::generate()));
+    let opts = Arc::new(NostrAndroidSignerProxyOptions::new()).tcp_port(free_port());
+    let res = NostrAndroidSignerProxy::with_opts(&unique_name(), callback, Arc::new(opts));
+    assert!(matches!(
+        res,
+        Err(AndroidSignerProxyError::InvalidArgument(_))
+    ));
+}
+
+#[test]
+fn test_tcp_client_requires_loopback() {
+    let addr = SocketAddr::from((Ipv4Addr::new(192, 168, 1, 2), 4000));
+    let res = AndroidSigner::tcp(addr, AndroidSignerOptions::new());
+    assert!(matches!(res, Err(AndroidSignerError::InvalidArgument(_))));
+}
+
+/// Unique socket path in the temp dir
+fn socket_path() -> PathBuf {
+    std::env::temp_dir().join(format!("{}.sock", unique_name()))
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn test_filesystem_socket() {
+    let path: PathBuf = socket_path();
+    let keys: Keys = Keys::generate();
+    let callback = Arc::new(KeysCallback::new(keys.clone()));
+    let opts = Arc::new(NostrAndroidSignerProxyOptions::new()).socket_mode(0o600);
+    let proxy = Arc::new(
+        NostrAndroidSignerProxy::with_path(path.to_str().unwrap(), callback, Arc::new(opts))
+            .unwrap(),
+    );
+    let handle = tokio::spawn({
+        let proxy = proxy.clone();
+        async move { proxy.run().await }
+    });
+
+    let signer = AndroidSigner::with_path(&path, AndroidSignerOptions::new()).unwrap();
+    assert_eq!(signer.get_public_key().await.unwrap(), keys.public_key());
+
+    let mode: u32 = fs::metadata(&path).unwrap().permissions().mode();
+    assert_eq!(mode & 0o777, 0o600);
+
+    // The private directory used to bind the socket has been removed
+    let mut dir_name = std::ffi::OsString::from(".");
+    dir_name.push(path.file_name().unwrap());
+    dir_name.push(format!(".{}", std::process::id()));
+    assert!(!path.with_file_name(dir_name).exists());
+
+    // The socket is removed on shutdown
+    proxy.shutdown();
+    handle.await.unwrap().unwrap();
+    assert!(!path.exists());
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn test_stale_socket() {
+    let path: PathBuf = socket_path();
+
+    // Left by a proxy that didn't shut down cleanly
+    drop(UnixListener::bind(&path).unwrap());
+    assert!(path.exists());
+
+    let keys: Keys = Keys::generate();
+    let callback = Arc::new(KeysCallback::new(keys.clone()));
+    let proxy = Arc::new(
+        NostrAndroidSignerProxy::with_path(
+            path.to_str().unwrap(),
+            callback,
+            Arc::new(NostrAndroidSignerProxyOptions::new()),
+        )
+        .unwrap(),
+    );
+    let handle = tokio::spawn({
+        let proxy = proxy.clone();
+        async move { proxy.run().await }
+    });
+
+    let signer = AndroidSigner::with_path(&path, AndroidSignerOptions::new()).unwrap();
+    assert_eq!(signer.get_public_key().await.unwrap(), keys.public_key());
+
+    proxy.shutdown();
+    handle.await.unwrap().unwrap();
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn test_socket_in_use() {
+    let path: PathBuf = socket_path();
+
+    // Another proxy is listening
+    let listener = UnixListener::bind(&path).unwrap();
+
+    let callback = Arc::new(KeysCallback::new(Keys::generate()));
+    let proxy = NostrAndroidSignerProxy::with_path(
+        path.to_str().unwrap(),
+        callback,
+        Arc::new(NostrAndroidSignerProxyOptions::new()),
+    )
+    .unwrap();
+    assert!(matches!(
+        proxy.run().await,
+        Err(AndroidSignerProxyError::IO(_))
+    ));
+    assert!(path.exists());
+
+    drop(listener);
+    fs::remove_file(&path).unwrap();
+}
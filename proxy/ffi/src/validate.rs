@@ -0,0 +1,136 @@
+//! Request validation
+//!
+//! Requests are validated before reaching the callback,
+//! so invalid ones are refused without prompting the user.
+
+use base64::Engine;
+use base64::engine::general_purpose;
+use nostr::{Event, JsonUtil, PublicKey, UnsignedEvent};
+
+use crate::error::AndroidSignerProxyError;
+
+/// NIP-04 IV size
+const NIP04_IV_SIZE: usize = 16;
+/// NIP-04 AES block size
+const NIP04_BLOCK_SIZE: usize = 16;
+
+/// NIP-44 v2 version byte
+const NIP44_V2: u8 = 2;
+/// NIP-44 v2 min/max plaintext size
+const NIP44_MIN_PLAINTEXT_SIZE: usize = 1;
+const NIP44_MAX_PLAINTEXT_SIZE: usize = 65535;
+/// NIP-44 v2 min/max base64 payload size
+const NIP44_MIN_PAYLOAD_SIZE: usize = 132;
+const NIP44_MAX_PAYLOAD_SIZE: usize = 87472;
+/// NIP-44 v2 min/max decoded payload size
+const NIP44_MIN_DECODED_PAYLOAD_SIZE: usize = 99;
+const NIP44_MAX_DECODED_PAYLOAD_SIZE: usize = 65603;
+
+fn invalid<S>(msg: S) -> AndroidSignerProxyError
+where
+    S: Into<String>,
+{
+    AndroidSignerProxyError::InvalidArgument(msg.into())
+}
+
+/// Parse a public key (hex or bech32)
+pub(crate) fn public_key(
+    name: &str,
+    public_key: &str,
+) -> Result<PublicKey, AndroidSignerProxyError> {
+    PublicKey::parse(public_key).map_err(|e| invalid(format!("Invalid {name}: {e}")))
+}
+
+/// Parse an unsigned event and check that it's authored by the current user
+pub(crate) fn unsigned_event(
+    json: &str,
+    current_user_public_key: &str,
+) -> Result<UnsignedEvent, AndroidSignerProxyError> {
+    let current_user_public_key: PublicKey =
+        public_key("current user public key", current_user_public_key)?;
+
+    let unsigned: UnsignedEvent = UnsignedEvent::from_json(json)
+        .map_err(|e| invalid(format!("Invalid unsigned event: {e}")))?;
+
+    // The ID is optional, but if set it must match the event
+    if unsigned.id.is_some() {
+        unsigned
+            .verify_id()
+            .map_err(|e| invalid(format!("Invalid unsigned event: {e}")))?;
+    }
+
+    if unsigned.pubkey != current_user_public_key {
+        return Err(invalid(format!(
+            "Unsigned event pubkey {} doesn't match the current user public key {}",
+            unsigned.pubkey, current_user_public_key
+        )));
+    }
+
+    Ok(unsigned)
+}
+
+/// Parse an event
+pub(crate) fn event(json: &str) -> Result<Event, AndroidSignerProxyError> {
+    Event::from_json(json).map_err(|e| invalid(format!("Invalid event: {e}")))
+}
+
+/// Check the format of a NIP-04 payload (`<base64 ciphertext>?iv=<base64 iv>`)
+pub(crate) fn nip04_payload(payload: &str) -> Result<(), AndroidSignerProxyError> {
+    let (ciphertext, iv) = payload
+        .split_once("?iv=")
+        .ok_or_else(|| invalid("Invalid NIP-04 payload: missing IV"))?;
+
+    let ciphertext: Vec<u8> = general_purpose::STANDARD
+        .decode(ciphertext)
+        .map_err(|_| invalid("Invalid NIP-04 payload: invalid base64 ciphertext"))?;
+    let iv: Vec<u8> = general_purpose::STANDARD
+        .decode(iv)
+        .map_err(|_| invalid("Invalid NIP-04 payload: invalid base64 IV"))?;
+
+    if iv.len() != NIP04_IV_SIZE {
+        return Err(invalid("Invalid NIP-04 payload: invalid IV size"));
+    }
+
+    if ciphertext.is_empty() || ciphertext.len() % NIP04_BLOCK_SIZE != 0 {
+        return Err(invalid("Invalid NIP-04 payload: invalid ciphertext size"));
+    }
+
+    Ok(())
+}
+
+/// Check that a plaintext can be encrypted with NIP-44
+pub(crate) fn nip44_plaintext(plaintext: &str) -> Result<(), AndroidSignerProxyError> {
+    let len: usize = plaintext.len();
+    if !(NIP44_MIN_PLAINTEXT_SIZE..=NIP44_MAX_PLAINTEXT_SIZE).contains(&len) {
+        return Err(invalid(format!(
+            "Invalid NIP-44 plaintext: size must be between {NIP44_MIN_PLAINTEXT_SIZE} and {NIP44_MAX_PLAINTEXT_SIZE} bytes"
+        )));
+    }
+
+    Ok(())
+}
+
+/// Check the format of a NIP-44 v2 payload
+pub(crate) fn nip44_payload(payload: &str) -> Result<(), AndroidSignerProxyError> {
+    if payload.starts_with('#') {
+        return Err(invalid("Invalid NIP-44 payload: unsupported version"));
+    }
+
+    if !(NIP44_MIN_PAYLOAD_SIZE..=NIP44_MAX_PAYLOAD_SIZE).contains(&payload.len()) {
+        return Err(invalid("Invalid NIP-44 payload: invalid size"));
+    }
+
+    let decoded: Vec<u8> = general_purpose::STANDARD
+        .decode(payload)
+        .map_err(|_| invalid("Invalid NIP-44 payload: invalid base64"))?;
+
+    if !(NIP44_MIN_DECODED_PAYLOAD_SIZE..=NIP44_MAX_DECODED_PAYLOAD_SIZE).contains(&decoded.len()) {
+        return Err(invalid("Invalid NIP-44 payload: invalid size"));
+    }
+
+    if decoded[0] != NIP44_V2 {
+        return Err(invalid("Invalid NIP-44 payload: unsupported version"));
+    }
+
+    Ok(())
+}
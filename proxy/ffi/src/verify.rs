@@ -0,0 +1,98 @@
+//! Signer response verification
+//!
+//! Responses of the signer app are verified before replying,
+//! so clients always get well-formed values.
+
+use nostr::{Event, EventId, JsonUtil, PublicKey, UnsignedEvent};
+
+use crate::error::AndroidSignerProxyError;
+
+fn invalid<S>(msg: S) -> AndroidSignerProxyError
+where
+    S: Into<String>,
+{
+    AndroidSignerProxyError::InvalidSignerResponse(msg.into())
+}
+
+/// Parse a public key returned by the signer
+///
+/// Signer apps return either hex or bech32 (`npub`) public keys.
+pub(crate) fn public_key(public_key: &str) -> Result<PublicKey, AndroidSignerProxyError> {
+    PublicKey::parse(public_key).map_err(|e| invalid(format!("Invalid public key: {e}")))
+}
+
+/// Parse and verify an event signed by the signer,
+/// checking that it matches the unsigned event.
+pub(crate) fn signed_event(
+    unsigned: &UnsignedEvent,
+    json: &str,
+) -> Result<Event, AndroidSignerProxyError> {
+    let event: Event =
+        Event::from_json(json).map_err(|e| invalid(format!("Invalid signed event: {e}")))?;
+
+    event
+        .verify()
+        .map_err(|e| invalid(format!("Invalid signed event: {e}")))?;
+
+    match signed_event_mismatch(unsigned, &event) {
+        Some(mismatch) => Err(invalid(format!("Signed event mismatch: {mismatch}"))),
+        None => Ok(event),
+    }
+}
+
+/// Compare a signed event with the unsigned event sent to the signer.
+///
+/// Returns a description of the mismatching fields, or `None` if the signer signed what was asked.
+/// The signature isn't checked: call [`Event::verify`] first.
+fn signed_event_mismatch(unsigned: &UnsignedEvent, event: &Event) -> Option<String> {
+    let mut mismatches: Vec<String> = Vec::new();
+
+    let expected_id: EventId = unsigned.id.unwrap_or_else(|| {
+        EventId::new(
+            &unsigned.pubkey,
+            &unsigned.created_at,
+            &unsigned.kind,
+            &unsigned.tags,
+            &unsigned.content,
+        )
+    });
+
+    if event.id != expected_id {
+        mismatches.push(format!("id: expected {expected_id}, found {}", event.id));
+    }
+
+    if event.pubkey != unsigned.pubkey {
+        mismatches.push(format!(
+            "pubkey: expected {}, found {}",
+            unsigned.pubkey, event.pubkey
+        ));
+    }
+
+    if event.created_at != unsigned.created_at {
+        mismatches.push(format!(
+            "created_at: expected {}, found {}",
+            unsigned.created_at, event.created_at
+        ));
+    }
+
+    if event.kind != unsigned.kind {
+        mismatches.push(format!(
+            "kind: expected {}, found {}",
+            unsigned.kind, event.kind
+        ));
+    }
+
+    if event.tags != unsigned.tags {
+        mismatches.push(String::from("tags differ"));
+    }
+
+    if event.content != unsigned.content {
+        mismatches.push(String::from("content differs"));
+    }
+
+    if mismatches.is_empty() {
+        None
+    } else {
+        Some(mismatches.join("; "))
+    }
+}
@@ -39,6 +39,8 @@ dependencies {
     implementation("net.java.dev.jna:jna:5.17.0@aar")
     implementation("org.jetbrains.kotlinx:kotlinx-coroutines-core:1.10.2")
     implementation("androidx.appcompat:appcompat:1.7.1")
+
+    testImplementation("junit:junit:4.13.2")
 }
 
 val version: String = "0.44.0"
@@ -1,6 +1,7 @@
 package rust.nostr.android.signer.proxy
 
 import android.app.Activity
+import android.content.ActivityNotFoundException
 import android.content.Context
 import android.content.Intent
 import android.database.Cursor
@@ -15,6 +16,10 @@ import kotlin.coroutines.resumeWithException
 import kotlin.coroutines.resume
 import rust.nostr.android.signer.proxy.ffi.NostrAndroidSignerProxyCallback
 import rust.nostr.android.signer.proxy.ffi.AndroidSignerProxyException
+import rust.nostr.android.signer.proxy.ffi.Permission
+import rust.nostr.android.signer.proxy.ffi.SignEventArgs
+import rust.nostr.android.signer.proxy.ffi.SignEventResult
+import rust.nostr.android.signer.proxy.ffi.permissionsToJson
 import rust.nostr.android.signer.proxy.types.*
 
 private class PendingRequest(
@@ -45,7 +50,7 @@ class NostrAndroidSignerProxyAdapter(private val context: Context, activity: Com
             requestQueue.removeAt(0)
 
             if (result.resultCode != Activity.RESULT_OK) {
-                val exception = AndroidSignerProxyException.Callback("Request rejected")
+                val exception = AndroidSignerProxyException.Rejected()
                 request.continuation.resumeWithException(exception)
             } else {
                 handleResult(request.type, result.data, request.continuation)
@@ -58,9 +63,10 @@ class NostrAndroidSignerProxyAdapter(private val context: Context, activity: Com
 
     // Intent builders for different request types
     private val intentBuilders = mapOf(
-        RequestType.GET_PUBLIC_KEY to { _ ->
+        RequestType.GET_PUBLIC_KEY to { params ->
             Intent(Intent.ACTION_VIEW, "nostrsigner:".toUri()).apply {
                 putExtra("type", RequestType.GET_PUBLIC_KEY.value)
+                params.permissions?.let { putExtra("permissions", it) }
             }
         },
         RequestType.SIGN_EVENT to { params ->
@@ -77,7 +83,18 @@ class NostrAndroidSignerProxyAdapter(private val context: Context, activity: Com
         RequestType.NIP04_ENCRYPT to createEncryptionIntentBuilder(RequestType.NIP04_ENCRYPT),
         RequestType.NIP04_DECRYPT to createDecryptionIntentBuilder(RequestType.NIP04_DECRYPT),
         RequestType.NIP44_ENCRYPT to createEncryptionIntentBuilder(RequestType.NIP44_ENCRYPT),
-        RequestType.NIP44_DECRYPT to createDecryptionIntentBuilder(RequestType.NIP44_DECRYPT)
+        RequestType.NIP44_DECRYPT to createDecryptionIntentBuilder(RequestType.NIP44_DECRYPT),
+        RequestType.DECRYPT_ZAP_EVENT to { params ->
+            RequestParamsValidator.validateZapDecryptionParams(params)
+
+            val packageName = sessionManager.getSignerPackage()
+
+            Intent(Intent.ACTION_VIEW, "nostrsigner:${params.event}".toUri()).apply {
+                packageName?.let { `package` = it }
+                putExtra("type", RequestType.DECRYPT_ZAP_EVENT.value)
+                putExtra("current_user", params.currentUserPubkey)
+            }
+        }
     )
 
     // Result handlers for different request types
@@ -93,7 +110,7 @@ class NostrAndroidSignerProxyAdapter(private val context: Context, activity: Com
                 continuation.resume(publicKey)
             } else {
                 continuation.resumeWithException(
-                    AndroidSignerProxyException.Callback("No public key received from signer")
+                    AndroidSignerProxyException.InvalidSignerResponse("No public key received from signer")
                 )
             }
         },
@@ -104,14 +121,15 @@ class NostrAndroidSignerProxyAdapter(private val context: Context, activity: Com
                 continuation.resume(signedEventJson)
             } else {
                 continuation.resumeWithException(
-                    AndroidSignerProxyException.Callback("No signature received from signer")
+                    AndroidSignerProxyException.InvalidSignerResponse("No signature received from signer")
                 )
             }
         },
         RequestType.NIP04_ENCRYPT to createEncryptionHandler(),
         RequestType.NIP04_DECRYPT to createDecryptionHandler(),
         RequestType.NIP44_ENCRYPT to createEncryptionHandler(),
-        RequestType.NIP44_DECRYPT to createDecryptionHandler()
+        RequestType.NIP44_DECRYPT to createDecryptionHandler(),
+        RequestType.DECRYPT_ZAP_EVENT to createDecryptionHandler()
     )
 
     private fun createEncryptionIntentBuilder(requestType: RequestType): IntentBuilder = { params ->
@@ -145,7 +163,7 @@ class NostrAndroidSignerProxyAdapter(private val context: Context, activity: Com
         if (result != null) {
             continuation.resume(result)
         } else {
-            continuation.resumeWithException(AndroidSignerProxyException.Callback("No ciphertext received from signer"))
+            continuation.resumeWithException(AndroidSignerProxyException.InvalidSignerResponse("No ciphertext received from signer"))
         }
     }
 
@@ -154,7 +172,7 @@ class NostrAndroidSignerProxyAdapter(private val context: Context, activity: Com
         if (result != null) {
             continuation.resume(result)
         } else {
-            continuation.resumeWithException(AndroidSignerProxyException.Callback("No plaintext received from signer"))
+            continuation.resumeWithException(AndroidSignerProxyException.InvalidSignerResponse("No plaintext received from signer"))
         }
     }
 
@@ -186,7 +204,7 @@ class NostrAndroidSignerProxyAdapter(private val context: Context, activity: Com
 
         return result.use { cursor ->
             if (cursor.getColumnIndex("rejected") > -1) {
-                throw AndroidSignerProxyException.Callback("Request rejected")
+                throw AndroidSignerProxyException.Rejected()
             }
 
             if (cursor.moveToFirst()) {
@@ -216,7 +234,7 @@ class NostrAndroidSignerProxyAdapter(private val context: Context, activity: Com
                         arrayOf("login")
                     ) { cursor ->
                         val index: Int = cursor.getColumnIndex("result")
-                        if (index < 0) null
+                        if (index < 0) return@queryContentResolver null
                         cursor.getString(index)
                     }
                 }
@@ -236,7 +254,7 @@ class NostrAndroidSignerProxyAdapter(private val context: Context, activity: Com
                         array
                     ) { cursor ->
                         val index: Int = cursor.getColumnIndex("event")
-                        if (index < 0) null
+                        if (index < 0) return@queryContentResolver null
                         cursor.getString(index)
                     }
                 }
@@ -256,7 +274,7 @@ class NostrAndroidSignerProxyAdapter(private val context: Context, activity: Com
                         array
                     ) { cursor ->
                         val index: Int = cursor.getColumnIndex("result")
-                        if (index < 0) null
+                        if (index < 0) return@queryContentResolver null
                         cursor.getString(index)
                     }
                 }
@@ -276,7 +294,7 @@ class NostrAndroidSignerProxyAdapter(private val context: Context, activity: Com
                         array
                     ) { cursor ->
                         val index: Int = cursor.getColumnIndex("result")
-                        if (index < 0) null
+                        if (index < 0) return@queryContentResolver null
                         cursor.getString(index)
                     }
                 }
@@ -296,7 +314,7 @@ class NostrAndroidSignerProxyAdapter(private val context: Context, activity: Com
                         array
                     ) { cursor ->
                         val index: Int = cursor.getColumnIndex("result")
-                        if (index < 0) null
+                        if (index < 0) return@queryContentResolver null
                         cursor.getString(index)
                     }
                 }
@@ -316,7 +334,27 @@ class NostrAndroidSignerProxyAdapter(private val context: Context, activity: Com
                         array
                     ) { cursor ->
                         val index: Int = cursor.getColumnIndex("result")
-                        if (index < 0) null
+                        if (index < 0) return@queryContentResolver null
+                        cursor.getString(index)
+                    }
+                }
+
+                RequestType.DECRYPT_ZAP_EVENT -> {
+                    // Validate zap decryption params
+                    RequestParamsValidator.validateZapDecryptionParams(params)
+
+                    val array = arrayOf(
+                        params.event!!,
+                        "",
+                        params.currentUserPubkey!!
+                    )
+
+                    queryContentResolver(
+                        "DECRYPT_ZAP_EVENT",
+                        array
+                    ) { cursor ->
+                        val index: Int = cursor.getColumnIndex("result")
+                        if (index < 0) return@queryContentResolver null
                         cursor.getString(index)
                     }
                 }
@@ -329,7 +367,11 @@ class NostrAndroidSignerProxyAdapter(private val context: Context, activity: Com
         params: RequestParams = RequestParams()
     ): String = withContext(Dispatchers.Main) {
         // First, try content resolver
-        val contentResolverResult = tryContentResolver(requestType, params)
+        val contentResolverResult = try {
+            tryContentResolver(requestType, params)
+        } catch (e: InvalidRequestParamsException) {
+            throw AndroidSignerProxyException.InvalidArgument(e.message ?: "Invalid request params")
+        }
         if (contentResolverResult != null) {
             return@withContext contentResolverResult
         }
@@ -337,6 +379,14 @@ class NostrAndroidSignerProxyAdapter(private val context: Context, activity: Com
         Log.d(TAG, "Content resolver returned null, trying intent launcher")
 
         // If content resolver returns null, fall back to intent
+        return@withContext queueIntent(requestType, params)
+    }
+
+    // Queue a request to be sent with an intent, skipping the content resolver
+    private suspend fun queueIntent(
+        requestType: RequestType,
+        params: RequestParams
+    ): String = withContext(Dispatchers.Main) {
         return@withContext suspendCancellableCoroutine { continuation ->
             val request = PendingRequest(requestType, continuation, params)
 
@@ -364,7 +414,23 @@ class NostrAndroidSignerProxyAdapter(private val context: Context, activity: Com
         val nextRequest = requestQueue.firstOrNull()
         if (nextRequest != null && !isRequestInProgress) {
             isRequestInProgress = true
-            launchRequest(nextRequest)
+            try {
+                launchRequest(nextRequest)
+            } catch (e: Exception) {
+                val exception = when (e) {
+                    is ActivityNotFoundException -> AndroidSignerProxyException.SignerNotInstalled()
+                    is InvalidRequestParamsException -> AndroidSignerProxyException.InvalidArgument(
+                        e.message ?: "Invalid request params"
+                    )
+                    else -> AndroidSignerProxyException.Callback(e.message ?: "Failed to launch signer")
+                }
+
+                // Fail the request and move on to the next one
+                requestQueue.removeAt(0)
+                isRequestInProgress = false
+                nextRequest.continuation.resumeWithException(exception)
+                processNextRequest()
+            }
         } else {
             isRequestInProgress = false
         }
@@ -398,8 +464,19 @@ class NostrAndroidSignerProxyAdapter(private val context: Context, activity: Com
         return@withContext infos.isNotEmpty()
     }
 
-    override suspend fun getPublicKey(): String {
-        return queueRequest(RequestType.GET_PUBLIC_KEY)
+    override suspend fun getPublicKey(permissions: List<Permission>, forceRefresh: Boolean): String {
+        val params = RequestParams.forLogin(permissionsToJson(permissions), forceRefresh)
+
+        // The signer must show the permissions or the account picker to the user
+        if (params.requiresIntent()) {
+            return queueIntent(RequestType.GET_PUBLIC_KEY, params)
+        }
+
+        return queueRequest(RequestType.GET_PUBLIC_KEY, params)
+    }
+
+    override suspend fun logout() {
+        sessionManager.clearSession()
     }
 
     override suspend fun signEvent(unsigned: String, currentUserPublicKey: String): String {
@@ -409,6 +486,42 @@ class NostrAndroidSignerProxyAdapter(private val context: Context, activity: Com
         )
     }
 
+    /**
+     * Sign multiple events.
+     *
+     * NIP-55 has no multi-event intent, so this is NOT batched into a single approval screen.
+     * All the events are first sent to the content resolver, which signs without any UI the ones
+     * the user already allowed; then each remaining event is signed with its own intent,
+     * so the user sees one approval screen per remaining event.
+     */
+    override suspend fun signEvents(events: List<SignEventArgs>): List<SignEventResult> {
+        val params = events.map { args ->
+            RequestParams.forSigning(args.unsigned, args.currentUserPublicKey)
+        }
+
+        // Silent pass: the events already allowed are signed by the content resolver
+        val silent: List<SignEventResult?> = params.map { p ->
+            try {
+                tryContentResolver(RequestType.SIGN_EVENT, p)?.let { SignEventResult.Success(it) }
+            } catch (e: InvalidRequestParamsException) {
+                SignEventResult.Failure(
+                    AndroidSignerProxyException.InvalidArgument(e.message ?: "Invalid request params")
+                )
+            } catch (e: AndroidSignerProxyException) {
+                SignEventResult.Failure(e)
+            }
+        }
+
+        // Each remaining event goes through the intent queue, so the failure of one doesn't affect the others
+        return params.mapIndexed { index, p ->
+            silent[index] ?: try {
+                SignEventResult.Success(queueIntent(RequestType.SIGN_EVENT, p))
+            } catch (e: AndroidSignerProxyException) {
+                SignEventResult.Failure(e)
+            }
+        }
+    }
+
     override suspend fun nip04Encrypt(
         currentUserPublicKey: String,
         otherUserPublicKey: String,
@@ -456,4 +569,15 @@ class NostrAndroidSignerProxyAdapter(private val context: Context, activity: Com
             RequestParams.forDecryption(currentUserPublicKey, otherUserPublicKey, ciphertext)
         )
     }
+
+    override suspend fun decryptZapEvent(event: String, currentUserPublicKey: String): String {
+        return queueRequest(
+            RequestType.DECRYPT_ZAP_EVENT,
+            RequestParams.forZapDecryption(event, currentUserPublicKey)
+        )
+    }
+
+    override suspend fun onConnectionRejected(uid: UInt?, pid: Int?) {
+        Log.w(TAG, "Rejected connection from uid=$uid, pid=$pid")
+    }
 }
@@ -9,15 +9,25 @@ import kotlinx.coroutines.SupervisorJob
 import kotlinx.coroutines.cancel
 import kotlinx.coroutines.launch
 import rust.nostr.android.signer.proxy.ffi.NostrAndroidSignerProxy
+import rust.nostr.android.signer.proxy.ffi.NostrAndroidSignerProxyOptions
+import java.security.SecureRandom
 
 class NostrAndroidSignerProxyServer(
     private val context: Context,
     private val activity: ComponentActivity,
     private val uniqueName: String
 ) {
+    private var proxy: NostrAndroidSignerProxy? = null
     private var serverJob: Job? = null
     private val coroutineScope = CoroutineScope(Dispatchers.IO + SupervisorJob())
 
+    /**
+     * Shared-secret token that the clients must send with every request.
+     *
+     * Hand it to the Rust side (i.e. via JNI) and set it with `AndroidSignerOptions::auth_token`.
+     */
+    val authToken: String = generateAuthToken()
+
     fun start() {
         // Construct adapter
         val adapter = NostrAndroidSignerProxyAdapter(context, activity)
@@ -26,16 +36,37 @@ class NostrAndroidSignerProxyServer(
         adapter.initialize()
 
         // Construct the proxy
-        val proxy = NostrAndroidSignerProxy(uniqueName, adapter)
+        val opts = NostrAndroidSignerProxyOptions().authToken(authToken)
+        val localProxy = NostrAndroidSignerProxy.withOpts(uniqueName, adapter, opts)
+        proxy = localProxy
 
         // Run
         serverJob = coroutineScope.launch {
-            proxy.run()
+            localProxy.run()
         }
     }
 
     fun stop() {
-        serverJob?.cancel()
-        coroutineScope.cancel()
+        // Refuse new requests and let the pending ones complete within the grace period
+        proxy?.shutdown()
+        proxy = null
+
+        // Release the resources once the proxy stopped
+        val job = serverJob
+        serverJob = null
+        coroutineScope.launch {
+            job?.join()
+            coroutineScope.cancel()
+        }
+    }
+
+    private companion object {
+        private const val AUTH_TOKEN_SIZE = 32
+
+        fun generateAuthToken(): String {
+            val bytes = ByteArray(AUTH_TOKEN_SIZE)
+            SecureRandom().nextBytes(bytes)
+            return bytes.joinToString("") { "%02x".format(it) }
+        }
     }
-}
\ No newline at end of file
+}
@@ -25,6 +25,14 @@ class SessionManager(private val context: Context) {
         }
     }
 
+    fun clearSession() {
+        cachedPackageName = null
+
+        prefs.edit {
+            remove(KEY_SIGNER_PACKAGE)
+        }
+    }
+
     private fun getStoredSignerPackage(): String? {
         return prefs.getString(KEY_SIGNER_PACKAGE, null)
     }
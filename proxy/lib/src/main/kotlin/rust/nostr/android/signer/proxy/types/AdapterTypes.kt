@@ -17,7 +17,8 @@ enum class RequestType(val value: String) {
     NIP04_ENCRYPT("nip04_encrypt"),
     NIP04_DECRYPT("nip04_decrypt"),
     NIP44_ENCRYPT("nip44_encrypt"),
-    NIP44_DECRYPT("nip44_decrypt");
+    NIP44_DECRYPT("nip44_decrypt"),
+    DECRYPT_ZAP_EVENT("decrypt_zap_event");
 
     companion object {
         /**
@@ -37,8 +38,19 @@ data class RequestParams(
     val otherPublicKey: String? = null,
     val plaintext: String? = null,
     val ciphertext: String? = null,
-    val unsigned: String? = null
+    val unsigned: String? = null,
+    val permissions: String? = null,
+    val event: String? = null,
+    val forceRefresh: Boolean = false
 ) {
+    /**
+     * Whether the request must be shown to the user with an intent, skipping the content resolver.
+     *
+     * The content resolver answers silently, so it can't carry the permissions to pre-approve
+     * nor let the user pick another account.
+     */
+    fun requiresIntent(): Boolean = permissions != null || forceRefresh
+
     companion object {
         /**
          * Creates RequestParams for encryption operations.
@@ -66,10 +78,23 @@ data class RequestParams(
             ciphertext = ciphertext
         )
 
+        /**
+         * Creates RequestParams for login, with the NIP-55 permissions JSON array.
+         *
+         * If `forceRefresh` is true, the user explicitly asked to log in.
+         */
+        fun forLogin(permissions: String?, forceRefresh: Boolean = false) =
+            RequestParams(permissions = permissions, forceRefresh = forceRefresh)
+
         /**
          * Creates RequestParams for signing events.
          */
         fun forSigning(unsigned: String, currentUserPubkey: String) = RequestParams(unsigned = unsigned, currentUserPubkey = currentUserPubkey)
+
+        /**
+         * Creates RequestParams for decrypting private zaps.
+         */
+        fun forZapDecryption(event: String, currentUserPubkey: String) = RequestParams(event = event, currentUserPubkey = currentUserPubkey)
     }
 }
 
@@ -125,5 +150,17 @@ object RequestParamsValidator {
             "Current user public key is required for sign_event request"
         )
     }
-}
 
+    /**
+     * Validates parameters for private zap decryption requests.
+     */
+    fun validateZapDecryptionParams(params: RequestParams) {
+        params.event ?: throw InvalidRequestParamsException(
+            "Event is required for decrypt_zap_event request"
+        )
+
+        params.currentUserPubkey ?: throw InvalidRequestParamsException(
+            "Current user public key is required for decrypt_zap_event request"
+        )
+    }
+}
\ No newline at end of file
@@ -0,0 +1,31 @@
+package rust.nostr.android.signer.proxy.types
+
+import org.junit.Assert.assertFalse
+import org.junit.Assert.assertTrue
+import org.junit.Test
+
+class RequestParamsTest {
+    @Test
+    fun loginWithPermissionsRequiresIntent() {
+        val params = RequestParams.forLogin("""[{"type":"sign_event","kind":1}]""")
+        assertTrue(params.requiresIntent())
+    }
+
+    @Test
+    fun explicitLoginRequiresIntent() {
+        assertTrue(RequestParams.forLogin(null, forceRefresh = true).requiresIntent())
+    }
+
+    @Test
+    fun loginWithoutPermissionsMayUseContentResolver() {
+        assertFalse(RequestParams.forLogin(null).requiresIntent())
+    }
+
+    @Test
+    fun otherRequestsMayUseContentResolver() {
+        assertFalse(RequestParams.forSigning("{}", "pubkey").requiresIntent())
+        assertFalse(RequestParams.forEncryption("pubkey", "other", "hello").requiresIntent())
+        assertFalse(RequestParams.forDecryption("pubkey", "other", "ciphertext").requiresIntent())
+        assertFalse(RequestParams.forZapDecryption("{}", "pubkey").requiresIntent())
+    }
+}
@@ -0,0 +1,110 @@
+//! Latency of fast requests while a slow (interactive) request is in flight.
+//!
+//! Run with `cargo bench -p nostr-android-signer --bench concurrency`.
+
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+use nostr_android_signer::prelude::*;
+use nostr_android_signer_mock::prelude::*;
+use tokio::sync::Mutex;
+
+const UNIQUE_NAME: &str = "bench_concurrency";
+/// Simulated time the user takes to approve an intent
+const INTERACTIVE_DELAY: Duration = Duration::from_millis(500);
+/// Number of fast requests to measure
+const ROUNDS: u32 = 20;
+
+/// Measure the average and max latency of `ROUNDS` NIP-44 decryptions.
+///
+/// If `lock` is set, each request acquires it first, like the old global client mutex did.
+async fn measure(signer: &AndroidSigner, lock: Option<&Mutex<()>>) -> (Duration, Duration) {
+    let other: Keys = Keys::generate();
+    let ciphertext: String = other
+        .nip44_encrypt(&signer.get_public_key().await.unwrap(), "payload")
+        .await
+        .unwrap();
+    let other: PublicKey = other.public_key();
+    let mut total: Duration = Duration::ZERO;
+    let mut max: Duration = Duration::ZERO;
+
+    for _ in 0..ROUNDS {
+        let start: Instant = Instant::now();
+        let _guard = match lock {
+            Some(lock) => Some(lock.lock().await),
+            None => None,
+        };
+        signer.nip44_decrypt(&other, &ciphertext).await.unwrap();
+        let elapsed: Duration = start.elapsed();
+        total += elapsed;
+        max = max.max(elapsed);
+    }
+
+    (total / ROUNDS, max)
+}
+
+/// Measure while a slow `sign_event` is in flight.
+async fn measure_during_sign(
+    signer: &AndroidSigner,
+    lock: Option<Arc<Mutex<()>>>,
+) -> (Duration, Duration) {
+    let public_key: PublicKey = signer.get_public_key().await.unwrap();
+
+    let sign = {
+        let signer: AndroidSigner = signer.clone();
+        let lock: Option<Arc<Mutex<()>>> = lock.clone();
+        tokio::spawn(async move {
+            let _guard = match &lock {
+                Some(lock) => Some(lock.lock().await),
+                None => None,
+            };
+            let unsigned: UnsignedEvent = EventBuilder::text_note("Hello").build(public_key);
+            signer.sign_event(unsigned).await.unwrap();
+        })
+    };
+
+    // Let the sign request start
+    tokio::time::sleep(Duration::from_millis(50)).await;
+
+    let latency: (Duration, Duration) = measure(signer, lock.as_deref()).await;
+
+    sign.await.unwrap();
+
+    latency
+}
+
+#[tokio::main]
+async fn main() {
+    // Mocked proxy: signing is slow (intent), everything else is fast (content resolver)
+    let proxy: MockProxy = MockProxy::new(UNIQUE_NAME, Keys::generate()).unwrap();
+    proxy
+        .set_behavior(Rpc::SignEvent, Behavior::approve().delay(INTERACTIVE_DELAY))
+        .await;
+    proxy.spawn().unwrap();
+
+    let signer: AndroidSigner =
+        AndroidSigner::new(UNIQUE_NAME, AndroidSignerOptions::default()).unwrap();
+
+    // Warm up the connection
+    signer.get_public_key().await.unwrap();
+
+    let idle = measure(&signer, None).await;
+    let concurrent = measure_during_sign(&signer, None).await;
+    let serialized = measure_during_sign(&signer, Some(Arc::new(Mutex::new(())))).await;
+
+    println!(
+        "nip44_decrypt latency over {ROUNDS} rounds (sign_event takes {INTERACTIVE_DELAY:?}):"
+    );
+    println!(
+        "  idle:                       avg {:?}, max {:?}",
+        idle.0, idle.1
+    );
+    println!(
+        "  during sign_event:          avg {:?}, max {:?}",
+        concurrent.0, concurrent.1
+    );
+    println!(
+        "  during sign_event (locked): avg {:?}, max {:?}",
+        serialized.0, serialized.1
+    );
+}
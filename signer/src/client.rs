@@ -1,235 +1,565 @@
 //! Android signer client
 
 use std::borrow::Cow;
+use std::future::Future;
+use std::net::SocketAddr;
 use std::os::unix::net::UnixStream as StdUnixStream;
+use std::path::Path;
 use std::sync::Arc;
+use std::time::Duration;
 
 use hyper_util::rt::TokioIo;
 use nostr::prelude::*;
 use nostr_android_signer_proto::android_signer_client::AndroidSignerClient;
 use nostr_android_signer_proto::{
-    GetPublicKeyReply, GetPublicKeyRequest, IsExternalSignerInstalledReply,
-    IsExternalSignerInstalledRequest, Nip04DecryptReply, Nip04DecryptRequest, Nip04EncryptReply,
-    Nip04EncryptRequest, Nip44DecryptReply, Nip44DecryptRequest, Nip44EncryptReply,
-    Nip44EncryptRequest, SignEventReply, SignEventRequest,
+    AUTH_TOKEN_METADATA_KEY, DecryptZapEventReply, DecryptZapEventRequest, GetPublicKeyReply,
+    GetPublicKeyRequest, IsExternalSignerInstalledReply, IsExternalSignerInstalledRequest,
+    LogoutRequest, Nip04DecryptReply, Nip04DecryptRequest, Nip04EncryptReply, Nip04EncryptRequest,
+    Nip44DecryptReply, Nip44DecryptRequest, Nip44EncryptReply, Nip44EncryptRequest,
+    Permission as ProtoPermission, SignEventReply, SignEventRequest, SignEventsReply,
+    SignEventsRequest, sign_events_result,
 };
-use tokio::net::UnixStream as TokioUnixStream;
-use tokio::sync::{Mutex, OnceCell};
+use tokio::net::{TcpStream, UnixStream as TokioUnixStream};
+use tokio::sync::{Mutex, RwLock, watch};
+use tonic::metadata::AsciiMetadataValue;
 use tonic::transport::{Channel, Endpoint, Uri};
-use tonic::{Request, Response};
+use tonic::{Code, Request, Response, Status};
 use tower::service_fn;
 use uds::{UnixSocketAddr, UnixStreamExt};
 
 use crate::error::Error;
+use crate::options::AndroidSignerOptions;
+use crate::state::ConnectionState;
+
+/// The client is cheap to clone and all clones share the same underlying channel,
+/// so requests are multiplexed over HTTP/2 without waiting for each other.
+type SharedClient = Arc<AndroidSignerClient<Channel>>;
+
+/// Address of the proxy
+#[derive(Debug, Clone, Copy)]
+enum ProxyAddr {
+    /// UNIX socket
+    Unix(UnixSocketAddr),
+    /// Loopback TCP socket
+    Tcp(SocketAddr),
+}
 
 /// Android signer client.
+///
+/// Requests are sent concurrently over a single connection:
+/// a request waiting for the user approval doesn't block the others.
+/// The proxy takes care of serializing the requests that need to launch an intent.
+///
+/// The [`NostrSigner`] methods act as the current user
+/// (see [`AndroidSigner::login`] and [`AndroidSigner::switch_account`]):
+/// [`NostrSigner::sign_event`] refuses the events of other authors.
+/// To use another account of the signer app, see [`AndroidSigner::sign_event_as`],
+/// [`AndroidSigner::sign_events`] and the `*_as` methods.
 #[derive(Debug, Clone)]
 pub struct AndroidSigner {
-    /// UNIX socket address
-    socket_addr: UnixSocketAddr,
-    /// Timeout for requests
-    client: OnceCell<Arc<Mutex<AndroidSignerClient<Channel>>>>,
+    /// Proxy address
+    addr: ProxyAddr,
+    /// gRPC client
+    ///
+    /// `None` if not connected yet or if the transport broke.
+    client: Arc<Mutex<Option<SharedClient>>>,
+    /// Connection state
+    state: Arc<watch::Sender<ConnectionState>>,
     /// Current user public key
-    public_key: OnceCell<PublicKey>,
+    public_key: Arc<RwLock<Option<PublicKey>>>,
+    /// Lock held while requesting the public key, to avoid multiple prompts
+    login_lock: Arc<Mutex<()>>,
+    /// Auth token metadata value
+    auth_token: Option<AsciiMetadataValue>,
+    /// Options
+    opts: AndroidSignerOptions,
 }
 
 impl AndroidSigner {
     /// Construct a new Android signer.
+    ///
+    /// Connects to the proxy at the `nip55_proxy_<unique_name>` abstract UNIX socket.
     #[inline]
-    pub fn new(unique_name: &str) -> Result<Self, Error> {
+    pub fn new(unique_name: &str, opts: AndroidSignerOptions) -> Result<Self, Error> {
         let name: String = format!("nip55_proxy_{unique_name}");
+        let socket_addr: UnixSocketAddr = UnixSocketAddr::from_abstract(name.as_bytes())?;
+        Self::with_socket_addr(socket_addr, opts)
+    }
+
+    /// Construct a new Android signer that connects to the proxy at a UNIX socket address.
+    #[inline]
+    pub fn with_socket_addr(
+        socket_addr: UnixSocketAddr,
+        opts: AndroidSignerOptions,
+    ) -> Result<Self, Error> {
+        Self::with_addr(ProxyAddr::Unix(socket_addr), opts)
+    }
+
+    /// Construct a new Android signer that connects to the proxy at a filesystem UNIX socket.
+    #[inline]
+    pub fn with_path<P>(path: P, opts: AndroidSignerOptions) -> Result<Self, Error>
+    where
+        P: AsRef<Path>,
+    {
+        let socket_addr: UnixSocketAddr = UnixSocketAddr::from_path(path.as_ref())?;
+        Self::with_socket_addr(socket_addr, opts)
+    }
+
+    /// Construct a new Android signer that connects to the proxy over loopback TCP.
+    ///
+    /// Useful to reach the proxy of a device with `adb forward tcp:<port> localabstract:nip55_proxy_<unique-name>`,
+    /// or a proxy with a TCP listener.
+    ///
+    /// Only loopback addresses are allowed.
+    pub fn tcp(addr: SocketAddr, opts: AndroidSignerOptions) -> Result<Self, Error> {
+        if !addr.ip().is_loopback() {
+            return Err(Error::InvalidArgument(format!(
+                "{addr} is not a loopback address"
+            )));
+        }
+
+        Self::with_addr(ProxyAddr::Tcp(addr), opts)
+    }
+
+    fn with_addr(addr: ProxyAddr, opts: AndroidSignerOptions) -> Result<Self, Error> {
+        // Parse the auth token
+        let auth_token: Option<AsciiMetadataValue> = match &opts.auth_token {
+            Some(token) => {
+                let mut value = AsciiMetadataValue::try_from(token.as_str()).map_err(|_| {
+                    Error::InvalidArgument(String::from("auth token must be visible ASCII"))
+                })?;
+
+                // Keep it out of the debug output
+                value.set_sensitive(true);
+
+                Some(value)
+            }
+            None => None,
+        };
 
         Ok(Self {
-            socket_addr: UnixSocketAddr::from_abstract(name.as_bytes())?,
-            client: OnceCell::new(),
-            public_key: OnceCell::new(),
+            addr,
+            client: Arc::new(Mutex::new(None)),
+            state: Arc::new(watch::Sender::new(ConnectionState::Disconnected)),
+            public_key: Arc::new(RwLock::new(None)),
+            login_lock: Arc::new(Mutex::new(())),
+            auth_token,
+            opts,
         })
     }
 
-    async fn client(&self) -> Result<&Arc<Mutex<AndroidSignerClient<Channel>>>, Error> {
-        self.client
-            .get_or_try_init(|| async {
-                let socket_addr: UnixSocketAddr = self.socket_addr;
+    /// Build a request, attaching the auth token
+    fn request<T>(&self, message: T) -> Request<T> {
+        let mut req: Request<T> = Request::new(message);
+
+        if let Some(token) = &self.auth_token {
+            req.metadata_mut()
+                .insert(AUTH_TOKEN_METADATA_KEY, token.clone());
+        }
+
+        req
+    }
+
+    /// Get the current connection state.
+    #[inline]
+    pub fn state(&self) -> ConnectionState {
+        *self.state.borrow()
+    }
+
+    /// Watch the connection state.
+    #[inline]
+    pub fn watch_state(&self) -> watch::Receiver<ConnectionState> {
+        self.state.subscribe()
+    }
 
-                // We will ignore this uri because uds do not use it
-                let channel: Channel = Endpoint::try_from("unix://fake_uri")?
-                    .connect_with_connector(service_fn(move |_: Uri| async move {
-                        let stream: TokioUnixStream = connect(&socket_addr)?;
+    async fn client(&self) -> Result<SharedClient, Error> {
+        // Lock the slot, so only one task at time try to (re)connect
+        let mut slot = self.client.lock().await;
 
-                        Ok::<_, Error>(TokioIo::new(stream))
-                    }))
-                    .await?;
+        if let Some(client) = slot.as_ref() {
+            return Ok(client.clone());
+        }
 
+        self.state.send_replace(ConnectionState::Connecting);
+
+        match timeout(self.opts.connection_timeout, self.connect_with_backoff()).await {
+            Ok(channel) => {
                 // Construct client
                 let client: AndroidSignerClient<Channel> = AndroidSignerClient::new(channel);
+                let client: SharedClient = Arc::new(client);
 
-                Ok(Arc::new(Mutex::new(client)))
-            })
-            .await
+                *slot = Some(client.clone());
+
+                self.state.send_replace(ConnectionState::Ready);
+
+                Ok(client)
+            }
+            Err(e) => {
+                self.state.send_replace(ConnectionState::Disconnected);
+                Err(e)
+            }
+        }
     }
 
-    /// Check if an external signer is installed.
-    pub async fn is_external_signer_installed(&self) -> Result<bool, Error> {
+    async fn connect_with_backoff(&self) -> Result<Channel, Error> {
+        let mut delay: Duration = self.opts.min_reconnect_delay;
+
+        loop {
+            match connect_channel(self.addr, self.opts.connection_timeout).await {
+                Ok(channel) => return Ok(channel),
+                Err(e) if !self.opts.reconnect => return Err(e),
+                Err(_) => {
+                    tokio::time::sleep(delay).await;
+                    delay = (delay * 2).min(self.opts.max_reconnect_delay);
+                }
+            }
+        }
+    }
+
+    /// Drop the cached client, so the next request will reconnect.
+    async fn disconnect(&self, client: &SharedClient) {
+        let mut slot = self.client.lock().await;
+
+        // Another task may have already reconnected
+        if slot.as_ref().is_some_and(|c| Arc::ptr_eq(c, client)) {
+            *slot = None;
+            self.state.send_replace(ConnectionState::Disconnected);
+        }
+    }
+
+    /// Make a request, applying the timeout and handling transport failures.
+    ///
+    /// The request gets its own clone of the client, so requests don't wait for each other.
+    async fn call<F, Fut, T>(&self, duration: Duration, request: F) -> Result<T, Error>
+    where
+        F: FnOnce(AndroidSignerClient<Channel>) -> Fut,
+        Fut: Future<Output = Result<Response<T>, Status>>,
+    {
         // Get the client
-        let client = self.client().await?;
+        let shared: SharedClient = self.client().await?;
+        let client: AndroidSignerClient<Channel> = AndroidSignerClient::clone(&shared);
+
+        let res: Result<T, Error> = timeout(duration, async {
+            let res: Response<T> = request(client).await?;
+            Ok(res.into_inner())
+        })
+        .await;
+
+        // If the transport is broken, drop the client and mark as disconnected
+        if let Err(e) = &res {
+            if e.is_broken_transport() {
+                self.disconnect(&shared).await;
+            }
+        }
 
-        // Acquire the lock
-        let mut client = client.lock().await;
+        res
+    }
 
+    /// Check if an external signer is installed.
+    pub async fn is_external_signer_installed(&self) -> Result<bool, Error> {
         // Make the request
         let req: Request<IsExternalSignerInstalledRequest> =
-            Request::new(IsExternalSignerInstalledRequest {});
-        let res: Response<IsExternalSignerInstalledReply> =
-            client.is_external_signer_installed(req).await?;
-
-        // Unwrap the response
-        let inner: IsExternalSignerInstalledReply = res.into_inner();
+            self.request(IsExternalSignerInstalledRequest {});
+        let inner: IsExternalSignerInstalledReply = self
+            .call(self.opts.timeout, |mut client| async move {
+                client.is_external_signer_installed(req).await
+            })
+            .await?;
         Ok(inner.installed)
     }
 
-    async fn _get_public_key(&self) -> Result<&PublicKey, Error> {
-        self.public_key
-            .get_or_try_init(|| async {
-                // Get the client
-                let client = self.client().await?;
+    /// Get the current user public key, asking the signer if not logged in yet.
+    ///
+    /// Same as [`NostrSigner::get_public_key`], but returns the detailed [`Error`].
+    pub async fn public_key(&self) -> Result<PublicKey, Error> {
+        // Check if already logged in
+        if let Some(public_key) = *self.public_key.read().await {
+            return Ok(public_key);
+        }
+
+        // Acquire the login lock
+        let _guard = self.login_lock.lock().await;
 
-                // Acquire the lock
-                let mut client = client.lock().await;
+        // Another task may have logged in while waiting for the lock
+        if let Some(public_key) = *self.public_key.read().await {
+            return Ok(public_key);
+        }
 
-                // Make the request
-                let req: Request<GetPublicKeyRequest> = Request::new(GetPublicKeyRequest {});
-                let res: Response<GetPublicKeyReply> = client.get_public_key(req).await?;
+        self.request_public_key(false).await
+    }
 
-                // Unwrap the response
-                let inner: GetPublicKeyReply = res.into_inner();
-                Ok(PublicKey::parse(&inner.public_key)?)
+    /// Request the public key to the proxy
+    ///
+    /// If `force_refresh` is true, the proxy asks the signer even if it has a cached public key.
+    async fn request_public_key(&self, force_refresh: bool) -> Result<PublicKey, Error> {
+        // Make the request
+        let req: Request<GetPublicKeyRequest> = self.request(GetPublicKeyRequest {
+            permissions: self
+                .opts
+                .permissions
+                .iter()
+                .map(|p| ProtoPermission {
+                    method: p.method.to_string(),
+                    kind: p.kind.map(|k| k.as_u16() as u32),
+                })
+                .collect(),
+            force_refresh,
+        });
+        let inner: GetPublicKeyReply = self
+            .call(self.opts.interactive_timeout, |mut client| async move {
+                client.get_public_key(req).await
             })
-            .await
+            .await?;
+        let public_key: PublicKey = PublicKey::parse(&inner.public_key)?;
+
+        // Save the public key
+        *self.public_key.write().await = Some(public_key);
+
+        Ok(public_key)
     }
 
-    async fn _sign_event(&self, unsigned: UnsignedEvent) -> Result<Event, Error> {
-        // Get the client
-        let client = self.client().await?;
+    /// Log in, requesting the public key to the signer.
+    ///
+    /// The [permissions](AndroidSignerOptions::permissions) are requested too.
+    ///
+    /// Unlike [`NostrSigner::get_public_key`], this always asks the signer,
+    /// even if a public key is already cached by this client or by the proxy,
+    /// so the user can pick another account.
+    pub async fn login(&self) -> Result<PublicKey, Error> {
+        let _guard = self.login_lock.lock().await;
+        self.request_public_key(true).await
+    }
+
+    /// Log out, clearing the cached state of both the client and the proxy.
+    pub async fn logout(&self) -> Result<(), Error> {
+        let _guard = self.login_lock.lock().await;
+
+        // Clear the current user
+        *self.public_key.write().await = None;
+
+        // Make the request
+        let req: Request<LogoutRequest> = self.request(LogoutRequest {});
+        self.call(self.opts.timeout, |mut client| async move {
+            client.logout(req).await
+        })
+        .await?;
+
+        Ok(())
+    }
 
-        // Acquire the lock
-        let mut client = client.lock().await;
+    /// Switch the current user to another account, without asking the signer.
+    ///
+    /// The public key will be used as current user for all the next requests.
+    ///
+    /// Only this client is affected: the proxy keeps serving the last logged-in public key
+    /// to the other clients, until the next [`AndroidSigner::login`] or [`AndroidSigner::logout`].
+    pub async fn switch_account(&self, public_key: PublicKey) {
+        let _guard = self.login_lock.lock().await;
+        *self.public_key.write().await = Some(public_key);
+    }
+
+    /// Get the current user public key, if logged in.
+    #[inline]
+    pub async fn current_user_public_key(&self) -> Option<PublicKey> {
+        *self.public_key.read().await
+    }
 
+    /// Sign an event as the `pubkey` of the [`UnsignedEvent`].
+    ///
+    /// Unlike [`NostrSigner::sign_event`], the `pubkey` may be any account of the signer app,
+    /// not only the current user.
+    /// The signed event is always checked to have the requested `pubkey`.
+    pub async fn sign_event_as(&self, unsigned: UnsignedEvent) -> Result<Event, Error> {
         // Make the request
-        let req: Request<SignEventRequest> = Request::new(SignEventRequest {
+        let req: Request<SignEventRequest> = self.request(SignEventRequest {
             unsigned_event: unsigned.as_json(),
             current_user_public_key: unsigned.pubkey.to_hex(),
         });
-        let res: Response<SignEventReply> = client.sign_event(req).await?;
-
-        // Unwrap the response
-        let inner: SignEventReply = res.into_inner();
+        let inner: SignEventReply = self
+            .call(self.opts.interactive_timeout, |mut client| async move {
+                client.sign_event(req).await
+            })
+            .await?;
         let event: Event = Event::from_json(&inner.event)?;
 
         // Verify
         event.verify()?;
 
+        // Check that the signer signed what we asked
+        check_signed_event(&unsigned, &event)?;
+
         Ok(event)
     }
 
-    async fn _nip04_encrypt(
+    /// Sign multiple events with a single request.
+    ///
+    /// Each event is signed as its `pubkey`, like [`AndroidSigner::sign_event_as`].
+    /// Returns a result for each event, in the same order.
+    /// The outer error is returned only if the whole request failed.
+    pub async fn sign_events(
+        &self,
+        unsigned: Vec<UnsignedEvent>,
+    ) -> Result<Vec<Result<Event, Error>>, Error> {
+        if unsigned.is_empty() {
+            return Ok(Vec::new());
+        }
+
+        // Make the request
+        let req: Request<SignEventsRequest> = self.request(SignEventsRequest {
+            events: unsigned
+                .iter()
+                .map(|unsigned| SignEventRequest {
+                    unsigned_event: unsigned.as_json(),
+                    current_user_public_key: unsigned.pubkey.to_hex(),
+                })
+                .collect(),
+        });
+        let inner: SignEventsReply = self
+            .call(self.opts.interactive_timeout, |mut client| async move {
+                client.sign_events(req).await
+            })
+            .await?;
+
+        if inner.results.len() != unsigned.len() {
+            return Err(Error::Status(Status::internal(format!(
+                "Expected {} results, got {}",
+                unsigned.len(),
+                inner.results.len()
+            ))));
+        }
+
+        Ok(inner
+            .results
+            .into_iter()
+            .zip(unsigned.iter())
+            .map(|(res, unsigned)| match res.result {
+                Some(sign_events_result::Result::Event(json)) => {
+                    let event: Event = Event::from_json(json)?;
+
+                    // Verify
+                    event.verify()?;
+
+                    // Check that the signer signed what we asked
+                    check_signed_event(unsigned, &event)?;
+
+                    Ok(event)
+                }
+                Some(sign_events_result::Result::Error(e)) => match e.policy_rule {
+                    Some(rule) => Err(Error::PolicyDenied(rule)),
+                    None => Err(Error::from(Status::new(Code::from(e.code), e.message))),
+                },
+                None => Err(Error::Status(Status::internal("Missing result"))),
+            })
+            .collect())
+    }
+
+    /// Encrypt with NIP-04 as a specific current user.
+    pub async fn nip04_encrypt_as(
         &self,
         current_user_public_key: &PublicKey,
         public_key: &PublicKey,
         plaintext: &str,
     ) -> Result<String, Error> {
-        // Get the client
-        let client = self.client().await?;
-
-        // Acquire the lock
-        let mut client = client.lock().await;
-
         // Make the request
-        let req: Request<Nip04EncryptRequest> = Request::new(Nip04EncryptRequest {
+        let req: Request<Nip04EncryptRequest> = self.request(Nip04EncryptRequest {
             current_user_public_key: current_user_public_key.to_hex(),
             other_public_key: public_key.to_hex(),
             plaintext: plaintext.to_string(),
         });
-        let res: Response<Nip04EncryptReply> = client.nip04_encrypt(req).await?;
-
-        // Unwrap the response
-        let inner: Nip04EncryptReply = res.into_inner();
+        let inner: Nip04EncryptReply = self
+            .call(self.opts.interactive_timeout, |mut client| async move {
+                client.nip04_encrypt(req).await
+            })
+            .await?;
         Ok(inner.ciphertext)
     }
 
-    async fn _nip04_decrypt(
+    /// Decrypt with NIP-04 as a specific current user.
+    pub async fn nip04_decrypt_as(
         &self,
         current_user_public_key: &PublicKey,
         public_key: &PublicKey,
         ciphertext: &str,
     ) -> Result<String, Error> {
-        // Get the client
-        let client = self.client().await?;
-
-        // Acquire the lock
-        let mut client = client.lock().await;
-
         // Make the request
-        let req: Request<Nip04DecryptRequest> = Request::new(Nip04DecryptRequest {
+        let req: Request<Nip04DecryptRequest> = self.request(Nip04DecryptRequest {
             current_user_public_key: current_user_public_key.to_hex(),
             other_public_key: public_key.to_hex(),
             ciphertext: ciphertext.to_string(),
         });
-        let res: Response<Nip04DecryptReply> = client.nip04_decrypt(req).await?;
-
-        // Unwrap the response
-        let inner: Nip04DecryptReply = res.into_inner();
+        let inner: Nip04DecryptReply = self
+            .call(self.opts.interactive_timeout, |mut client| async move {
+                client.nip04_decrypt(req).await
+            })
+            .await?;
         Ok(inner.plaintext)
     }
 
-    async fn _nip44_encrypt(
+    /// Encrypt with NIP-44 as a specific current user.
+    pub async fn nip44_encrypt_as(
         &self,
         current_user_public_key: &PublicKey,
         public_key: &PublicKey,
         plaintext: &str,
     ) -> Result<String, Error> {
-        // Get the client
-        let client = self.client().await?;
-
-        // Acquire the lock
-        let mut client = client.lock().await;
-
         // Make the request
-        let req: Request<Nip44EncryptRequest> = Request::new(Nip44EncryptRequest {
+        let req: Request<Nip44EncryptRequest> = self.request(Nip44EncryptRequest {
             current_user_public_key: current_user_public_key.to_hex(),
             other_public_key: public_key.to_hex(),
             plaintext: plaintext.to_string(),
         });
-        let res: Response<Nip44EncryptReply> = client.nip44_encrypt(req).await?;
-
-        // Unwrap the response
-        let inner: Nip44EncryptReply = res.into_inner();
+        let inner: Nip44EncryptReply = self
+            .call(self.opts.interactive_timeout, |mut client| async move {
+                client.nip44_encrypt(req).await
+            })
+            .await?;
         Ok(inner.ciphertext)
     }
 
-    async fn _nip44_decrypt(
+    /// Decrypt with NIP-44 as a specific current user.
+    pub async fn nip44_decrypt_as(
         &self,
         current_user_public_key: &PublicKey,
         public_key: &PublicKey,
         ciphertext: &str,
     ) -> Result<String, Error> {
-        // Get the client
-        let client = self.client().await?;
-
-        // Acquire the lock
-        let mut client = client.lock().await;
-
         // Make the request
-        let req: Request<Nip44DecryptRequest> = Request::new(Nip44DecryptRequest {
+        let req: Request<Nip44DecryptRequest> = self.request(Nip44DecryptRequest {
             current_user_public_key: current_user_public_key.to_hex(),
             other_public_key: public_key.to_hex(),
             ciphertext: ciphertext.to_string(),
         });
-        let res: Response<Nip44DecryptReply> = client.nip44_decrypt(req).await?;
-
-        // Unwrap the response
-        let inner: Nip44DecryptReply = res.into_inner();
+        let inner: Nip44DecryptReply = self
+            .call(self.opts.interactive_timeout, |mut client| async move {
+                client.nip44_decrypt(req).await
+            })
+            .await?;
         Ok(inner.plaintext)
     }
+
+    /// Decrypt a private zap, returning the inner zap request.
+    ///
+    /// <https://github.com/nostr-protocol/nips/blob/master/57.md>
+    pub async fn decrypt_zap_event(&self, event: &Event) -> Result<Event, Error> {
+        let current_user_public_key: PublicKey = self.public_key().await?;
+
+        // Make the request
+        let req: Request<DecryptZapEventRequest> = self.request(DecryptZapEventRequest {
+            event: event.as_json(),
+            current_user_public_key: current_user_public_key.to_hex(),
+        });
+        let inner: DecryptZapEventReply = self
+            .call(self.opts.interactive_timeout, |mut client| async move {
+                client.decrypt_zap_event(req).await
+            })
+            .await?;
+        let zap_request: Event = Event::from_json(&inner.event)?;
+
+        // Verify
+        zap_request.verify()?;
+
+        Ok(zap_request)
+    }
 }
 
 impl NostrSigner for AndroidSigner {
@@ -238,17 +568,20 @@ impl NostrSigner for AndroidSigner {
     }
 
     fn get_public_key(&self) -> BoxedFuture<Result<PublicKey, SignerError>> {
-        Box::pin(async move {
-            self._get_public_key()
-                .await
-                .copied()
-                .map_err(SignerError::backend)
-        })
+        Box::pin(async move { self.public_key().await.map_err(SignerError::backend) })
     }
 
     fn sign_event(&self, unsigned: UnsignedEvent) -> BoxedFuture<Result<Event, SignerError>> {
         Box::pin(async move {
-            self._sign_event(unsigned)
+            // Like any other signer, only sign as the current user
+            let current_user_public_key = self.public_key().await.map_err(SignerError::backend)?;
+            if unsigned.pubkey != current_user_public_key {
+                return Err(SignerError::backend(Error::InvalidArgument(String::from(
+                    "event pubkey is not the current user",
+                ))));
+            }
+
+            self.sign_event_as(unsigned)
                 .await
                 .map_err(SignerError::backend)
         })
@@ -260,9 +593,8 @@ impl NostrSigner for AndroidSigner {
         content: &'a str,
     ) -> BoxedFuture<'a, Result<String, SignerError>> {
         Box::pin(async move {
-            let current_user_public_key =
-                self._get_public_key().await.map_err(SignerError::backend)?;
-            self._nip04_encrypt(current_user_public_key, public_key, content)
+            let current_user_public_key = self.public_key().await.map_err(SignerError::backend)?;
+            self.nip04_encrypt_as(&current_user_public_key, public_key, content)
                 .await
                 .map_err(SignerError::backend)
         })
@@ -274,9 +606,8 @@ impl NostrSigner for AndroidSigner {
         encrypted_content: &'a str,
     ) -> BoxedFuture<'a, Result<String, SignerError>> {
         Box::pin(async move {
-            let current_user_public_key =
-                self._get_public_key().await.map_err(SignerError::backend)?;
-            self._nip04_decrypt(current_user_public_key, public_key, encrypted_content)
+            let current_user_public_key = self.public_key().await.map_err(SignerError::backend)?;
+            self.nip04_decrypt_as(&current_user_public_key, public_key, encrypted_content)
                 .await
                 .map_err(SignerError::backend)
         })
@@ -288,9 +619,8 @@ impl NostrSigner for AndroidSigner {
         content: &'a str,
     ) -> BoxedFuture<'a, Result<String, SignerError>> {
         Box::pin(async move {
-            let current_user_public_key =
-                self._get_public_key().await.map_err(SignerError::backend)?;
-            self._nip44_encrypt(current_user_public_key, public_key, content)
+            let current_user_public_key = self.public_key().await.map_err(SignerError::backend)?;
+            self.nip44_encrypt_as(&current_user_public_key, public_key, content)
                 .await
                 .map_err(SignerError::backend)
         })
@@ -302,17 +632,65 @@ impl NostrSigner for AndroidSigner {
         payload: &'a str,
     ) -> BoxedFuture<'a, Result<String, SignerError>> {
         Box::pin(async move {
-            let current_user_public_key =
-                self._get_public_key().await.map_err(SignerError::backend)?;
-            self._nip44_decrypt(current_user_public_key, public_key, payload)
+            let current_user_public_key = self.public_key().await.map_err(SignerError::backend)?;
+            self.nip44_decrypt_as(&current_user_public_key, public_key, payload)
                 .await
                 .map_err(SignerError::backend)
         })
     }
 }
 
+/// Check that the signed event matches the unsigned event sent to the signer.
+fn check_signed_event(unsigned: &UnsignedEvent, event: &Event) -> Result<(), Error> {
+    match signed_event_mismatch(unsigned, event) {
+        Some(mismatch) => Err(Error::SignedEventMismatch(mismatch)),
+        None => Ok(()),
+    }
+}
+
+async fn timeout<F, T>(timeout: Duration, future: F) -> Result<T, Error>
+where
+    F: Future<Output = Result<T, Error>>,
+{
+    tokio::time::timeout(timeout, future)
+        .await
+        .map_err(|_| Error::Timeout)?
+}
+
+async fn connect_channel(addr: ProxyAddr, connection_timeout: Duration) -> Result<Channel, Error> {
+    match addr {
+        ProxyAddr::Unix(socket_addr) => {
+            // We will ignore this uri because uds do not use it
+            let endpoint: Endpoint =
+                Endpoint::try_from("unix://fake_uri")?.connect_timeout(connection_timeout);
+
+            Ok(endpoint
+                .connect_with_connector(service_fn(move |_: Uri| async move {
+                    let stream: TokioUnixStream = connect(&socket_addr)?;
+
+                    Ok::<_, Error>(TokioIo::new(stream))
+                }))
+                .await?)
+        }
+        ProxyAddr::Tcp(addr) => {
+            let endpoint: Endpoint = Endpoint::try_from(format!("http://{addr}"))?
+                .connect_timeout(connection_timeout)
+                .tcp_nodelay(true);
+
+            Ok(endpoint
+                .connect_with_connector(service_fn(move |_: Uri| async move {
+                    let stream: TcpStream = TcpStream::connect(addr).await?;
+                    stream.set_nodelay(true)?;
+
+                    Ok::<_, Error>(TokioIo::new(stream))
+                }))
+                .await?)
+        }
+    }
+}
+
 fn connect(socket_addr: &UnixSocketAddr) -> Result<TokioUnixStream, Error> {
-    // Connect to the abstract socket
+    // Connect to the UNIX socket
     let std_stream: StdUnixStream = StdUnixStream::connect_to_unix_addr(socket_addr)?;
 
     // Moves the socket into nonblocking mode
@@ -321,3 +699,60 @@ fn connect(socket_addr: &UnixSocketAddr) -> Result<TokioUnixStream, Error> {
     // Convert to Tokio's async UnixStream
     Ok(TokioUnixStream::from_std(std_stream)?)
 }
+
+/// Compare a signed event with the unsigned event sent to the signer.
+///
+/// Returns a description of the mismatching fields, or `None` if the signer signed what was asked.
+/// The signature isn't checked: call [`Event::verify`] first.
+fn signed_event_mismatch(unsigned: &UnsignedEvent, event: &Event) -> Option<String> {
+    let mut mismatches: Vec<String> = Vec::new();
+
+    let expected_id: EventId = unsigned.id.unwrap_or_else(|| {
+        EventId::new(
+            &unsigned.pubkey,
+            &unsigned.created_at,
+            &unsigned.kind,
+            &unsigned.tags,
+            &unsigned.content,
+        )
+    });
+
+    if event.id != expected_id {
+        mismatches.push(format!("id: expected {expected_id}, found {}", event.id));
+    }
+
+    if event.pubkey != unsigned.pubkey {
+        mismatches.push(format!(
+            "pubkey: expected {}, found {}",
+            unsigned.pubkey, event.pubkey
+        ));
+    }
+
+    if event.created_at != unsigned.created_at {
+        mismatches.push(format!(
+            "created_at: expected {}, found {}",
+            unsigned.created_at, event.created_at
+        ));
+    }
+
+    if event.kind != unsigned.kind {
+        mismatches.push(format!(
+            "kind: expected {}, found {}",
+            unsigned.kind, event.kind
+        ));
+    }
+
+    if event.tags != unsigned.tags {
+        mismatches.push(String::from("tags differ"));
+    }
+
+    if event.content != unsigned.content {
+        mismatches.push(String::from("content differs"));
+    }
+
+    if mismatches.is_empty() {
+        None
+    } else {
+        Some(mismatches.join("; "))
+    }
+}
@@ -3,7 +3,8 @@
 use std::{fmt, io};
 
 use nostr::{event, key};
-use tonic::Status;
+use nostr_android_signer_proto::POLICY_RULE_METADATA_KEY;
+use tonic::{Code, Status};
 
 /// Android signer error.
 #[derive(Debug)]
@@ -18,12 +19,41 @@ pub enum Error {
     Keys(key::Error),
     /// Event error
     Event(event::Error),
+    /// The signed event doesn't match the requested unsigned event
+    SignedEventMismatch(String),
+    /// The proxy got an invalid response from the signer app
+    InvalidSignerResponse(String),
+    /// The proxy refused the auth token
+    Unauthenticated,
+    /// The user rejected the request
+    Rejected,
+    /// The request has been denied by a policy rule of the proxy
+    PolicyDenied(String),
+    /// No external signer app is installed
+    SignerNotInstalled,
+    /// The request has invalid arguments
+    InvalidArgument(String),
+    /// The request is not supported by the signer
+    Unsupported(String),
     /// Timeout
     Timeout,
+    /// The request has been cancelled
+    Cancelled,
 }
 
 impl std::error::Error for Error {}
 
+impl Error {
+    /// Check if the error is caused by a broken connection with the proxy
+    pub(crate) fn is_broken_transport(&self) -> bool {
+        match self {
+            Self::IO(..) | Self::Transport(..) => true,
+            Self::Status(status) => status.code() == Code::Unavailable,
+            _ => false,
+        }
+    }
+}
+
 impl fmt::Display for Error {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         match self {
@@ -32,7 +62,16 @@ impl fmt::Display for Error {
             Self::Status(status) => f.write_str(status.message()),
             Self::Keys(e) => e.fmt(f),
             Self::Event(e) => e.fmt(f),
+            Self::SignedEventMismatch(e) => write!(f, "Signed event mismatch: {e}"),
+            Self::InvalidSignerResponse(e) => f.write_str(e),
+            Self::Unauthenticated => f.write_str("Unauthenticated"),
+            Self::Rejected => f.write_str("Request rejected"),
+            Self::PolicyDenied(rule) => write!(f, "Request denied by policy rule '{rule}'"),
+            Self::SignerNotInstalled => f.write_str("Signer not installed"),
+            Self::InvalidArgument(e) => write!(f, "Invalid argument: {e}"),
+            Self::Unsupported(e) => write!(f, "Unsupported: {e}"),
             Self::Timeout => f.write_str("Timeout"),
+            Self::Cancelled => f.write_str("Request cancelled"),
         }
     }
 }
@@ -51,7 +90,24 @@ impl From<tonic::transport::Error> for Error {
 
 impl From<Status> for Error {
     fn from(s: Status) -> Self {
-        Self::Status(s)
+        match s.code() {
+            Code::Unauthenticated => Self::Unauthenticated,
+            Code::PermissionDenied => match s.metadata().get_bin(POLICY_RULE_METADATA_KEY) {
+                Some(rule) => Self::PolicyDenied(
+                    rule.to_bytes()
+                        .map(|rule| String::from_utf8_lossy(&rule).into_owned())
+                        .unwrap_or_default(),
+                ),
+                None => Self::Rejected,
+            },
+            Code::FailedPrecondition => Self::SignerNotInstalled,
+            Code::InvalidArgument => Self::InvalidArgument(s.message().to_string()),
+            Code::Unimplemented => Self::Unsupported(s.message().to_string()),
+            Code::DeadlineExceeded => Self::Timeout,
+            Code::Cancelled => Self::Cancelled,
+            Code::DataLoss => Self::InvalidSignerResponse(s.message().to_string()),
+            _ => Self::Status(s),
+        }
     }
 }
 
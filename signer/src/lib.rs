@@ -7,4 +7,10 @@
 
 pub mod client;
 pub mod error;
+pub mod options;
+pub mod permission;
 pub mod prelude;
+pub mod state;
+
+#[cfg(test)]
+mod tests;
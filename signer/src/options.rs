@@ -0,0 +1,148 @@
+//! Android signer options
+
+use std::fmt;
+use std::time::Duration;
+
+use crate::permission::Permission;
+
+const DEFAULT_CONNECTION_TIMEOUT: Duration = Duration::from_secs(10);
+const DEFAULT_TIMEOUT: Duration = Duration::from_secs(10);
+const DEFAULT_INTERACTIVE_TIMEOUT: Duration = Duration::from_secs(120);
+const DEFAULT_MIN_RECONNECT_DELAY: Duration = Duration::from_millis(250);
+const DEFAULT_MAX_RECONNECT_DELAY: Duration = Duration::from_secs(5);
+
+/// Android signer options
+#[derive(Clone, PartialEq, Eq)]
+pub struct AndroidSignerOptions {
+    pub(crate) connection_timeout: Duration,
+    pub(crate) timeout: Duration,
+    pub(crate) interactive_timeout: Duration,
+    pub(crate) reconnect: bool,
+    pub(crate) min_reconnect_delay: Duration,
+    pub(crate) max_reconnect_delay: Duration,
+    pub(crate) permissions: Vec<Permission>,
+    pub(crate) auth_token: Option<String>,
+}
+
+impl fmt::Debug for AndroidSignerOptions {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("AndroidSignerOptions")
+            .field("connection_timeout", &self.connection_timeout)
+            .field("timeout", &self.timeout)
+            .field("interactive_timeout", &self.interactive_timeout)
+            .field("reconnect", &self.reconnect)
+            .field("min_reconnect_delay", &self.min_reconnect_delay)
+            .field("max_reconnect_delay", &self.max_reconnect_delay)
+            .field("permissions", &self.permissions)
+            // Never print the token
+            .field(
+                "auth_token",
+                &self.auth_token.as_ref().map(|_| "<redacted>"),
+            )
+            .finish()
+    }
+}
+
+impl Default for AndroidSignerOptions {
+    fn default() -> Self {
+        Self {
+            connection_timeout: DEFAULT_CONNECTION_TIMEOUT,
+            timeout: DEFAULT_TIMEOUT,
+            interactive_timeout: DEFAULT_INTERACTIVE_TIMEOUT,
+            reconnect: true,
+            min_reconnect_delay: DEFAULT_MIN_RECONNECT_DELAY,
+            max_reconnect_delay: DEFAULT_MAX_RECONNECT_DELAY,
+            permissions: Vec::new(),
+            auth_token: None,
+        }
+    }
+}
+
+impl AndroidSignerOptions {
+    /// New default options
+    #[inline]
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Timeout for connecting to the proxy (default: 10 secs)
+    ///
+    /// If reconnection is enabled, this includes all the attempts.
+    #[inline]
+    pub fn connection_timeout(mut self, timeout: Duration) -> Self {
+        self.connection_timeout = timeout;
+        self
+    }
+
+    /// Timeout for requests that never require user interaction (default: 10 secs)
+    ///
+    /// Used for calls that are always answered without prompting the user,
+    /// like [`AndroidSigner::is_external_signer_installed`](crate::client::AndroidSigner::is_external_signer_installed).
+    #[inline]
+    pub fn timeout(mut self, timeout: Duration) -> Self {
+        self.timeout = timeout;
+        self
+    }
+
+    /// Timeout for requests that may require user interaction (default: 120 secs)
+    ///
+    /// The proxy first tries the content resolver and falls back to an intent,
+    /// which shows a prompt to the user, so this should be long enough for the user to approve the request.
+    #[inline]
+    pub fn interactive_timeout(mut self, timeout: Duration) -> Self {
+        self.interactive_timeout = timeout;
+        self
+    }
+
+    /// Automatically retry to connect if the proxy is unreachable (default: true)
+    ///
+    /// When the transport breaks (i.e., the proxy restarted), the connection is always dropped
+    /// and re-established on the next request.
+    /// This option controls if the connection is retried, with an exponential backoff,
+    /// until the [`connection_timeout`](Self::connection_timeout) expires.
+    #[inline]
+    pub fn reconnect(mut self, reconnect: bool) -> Self {
+        self.reconnect = reconnect;
+        self
+    }
+
+    /// Delay before the first reconnection attempt (default: 250 ms)
+    ///
+    /// The delay doubles after every failed attempt, up to [`max_reconnect_delay`](Self::max_reconnect_delay).
+    #[inline]
+    pub fn min_reconnect_delay(mut self, delay: Duration) -> Self {
+        self.min_reconnect_delay = delay;
+        self
+    }
+
+    /// Max delay between reconnection attempts (default: 5 secs)
+    #[inline]
+    pub fn max_reconnect_delay(mut self, delay: Duration) -> Self {
+        self.max_reconnect_delay = delay;
+        self
+    }
+
+    /// Permissions to request during login (default: none)
+    ///
+    /// The user can pre-approve them once, instead of approving every request.
+    #[inline]
+    pub fn permissions<I>(mut self, permissions: I) -> Self
+    where
+        I: IntoIterator<Item = Permission>,
+    {
+        self.permissions = permissions.into_iter().collect();
+        self
+    }
+
+    /// Shared-secret token to authenticate to the proxy (default: none)
+    ///
+    /// Must match the token configured on the proxy, otherwise all requests fail with [`Error::Unauthenticated`](crate::error::Error::Unauthenticated).
+    #[inline]
+    pub fn auth_token<S>(mut self, token: S) -> Self
+    where
+        S: Into<String>,
+    {
+        self.auth_token = Some(token.into());
+        self
+    }
+}
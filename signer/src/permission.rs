@@ -0,0 +1,72 @@
+//! NIP-55 permissions
+//!
+//! <https://github.com/nostr-protocol/nips/blob/master/55.md>
+
+use std::fmt;
+
+use nostr::Kind;
+
+/// Signer method
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Method {
+    /// Sign event
+    SignEvent,
+    /// NIP-04 encrypt
+    Nip04Encrypt,
+    /// NIP-04 decrypt
+    Nip04Decrypt,
+    /// NIP-44 encrypt
+    Nip44Encrypt,
+    /// NIP-44 decrypt
+    Nip44Decrypt,
+    /// Decrypt private zap
+    DecryptZapEvent,
+}
+
+impl fmt::Display for Method {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+impl Method {
+    /// Get as `&str`
+    pub fn as_str(&self) -> &str {
+        match self {
+            Self::SignEvent => "sign_event",
+            Self::Nip04Encrypt => "nip04_encrypt",
+            Self::Nip04Decrypt => "nip04_decrypt",
+            Self::Nip44Encrypt => "nip44_encrypt",
+            Self::Nip44Decrypt => "nip44_decrypt",
+            Self::DecryptZapEvent => "decrypt_zap_event",
+        }
+    }
+}
+
+/// Permission to pre-approve during login
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct Permission {
+    /// Method
+    pub method: Method,
+    /// Event kind
+    ///
+    /// Only meaningful for [`Method::SignEvent`]: if `None`, all kinds are allowed.
+    pub kind: Option<Kind>,
+}
+
+impl Permission {
+    /// New permission for a method
+    #[inline]
+    pub fn new(method: Method) -> Self {
+        Self { method, kind: None }
+    }
+
+    /// Permission to sign events of a specific kind
+    #[inline]
+    pub fn sign_event(kind: Kind) -> Self {
+        Self {
+            method: Method::SignEvent,
+            kind: Some(kind),
+        }
+    }
+}
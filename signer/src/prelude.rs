@@ -5,6 +5,10 @@
 #![doc(hidden)]
 
 pub use nostr::prelude::*;
+pub use uds::UnixSocketAddr;
 
 pub use crate::client::{self, *};
 pub use crate::error::{self, *};
+pub use crate::options::{self, *};
+pub use crate::permission::{self, *};
+pub use crate::state::{self, *};
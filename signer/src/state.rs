@@ -0,0 +1,12 @@
+//! Connection state
+
+/// Connection state with the proxy
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum ConnectionState {
+    /// Connecting to the proxy
+    Connecting,
+    /// Connected and ready to handle requests
+    Ready,
+    /// Not connected
+    Disconnected,
+}
@@ -0,0 +1,535 @@
+//! Client tests
+//!
+//! The `AndroidSigner` talks to the mock proxy, scripted to misbehave.
+
+use std::path::PathBuf;
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+use nostr::prelude::*;
+use nostr_android_signer_mock::prelude::{
+    ApprovalHook, Behavior, Code, EventField, MockProxy, Rpc,
+};
+use nostr_android_signer_proto::POLICY_RULE_METADATA_KEY;
+use tokio::sync::oneshot;
+use tokio::task::JoinHandle;
+use tonic::Status;
+use tonic::metadata::MetadataValue;
+
+use crate::prelude::{AndroidSigner, AndroidSignerOptions, ConnectionState, Error};
+
+/// Check the kind of an error
+type ErrorCheck = fn(&Error) -> bool;
+
+static SOCKET_COUNTER: AtomicUsize = AtomicUsize::new(0);
+
+/// Random unique name, so tests can run in parallel
+fn unique_name() -> String {
+    let n: usize = SOCKET_COUNTER.fetch_add(1, Ordering::SeqCst);
+    let nonce: String = Keys::generate().public_key().to_hex();
+    format!("signer_test_{}_{n}_{}", std::process::id(), &nonce[..8])
+}
+
+/// A mock proxy and a signer connected to it
+struct Harness {
+    mock: MockProxy,
+    signer: AndroidSigner,
+    handle: JoinHandle<Result<(), nostr_android_signer_mock::prelude::Error>>,
+}
+
+impl Harness {
+    fn new() -> Self {
+        Self::with_opts(AndroidSignerOptions::new())
+    }
+
+    fn with_opts(opts: AndroidSignerOptions) -> Self {
+        let name: String = unique_name();
+        let mock = MockProxy::new(&name, Keys::generate()).unwrap();
+        let handle = mock.spawn().unwrap();
+        let signer = AndroidSigner::new(&name, opts).unwrap();
+        Self {
+            mock,
+            signer,
+            handle,
+        }
+    }
+
+    fn unsigned(&self) -> UnsignedEvent {
+        EventBuilder::text_note("hello").build(self.mock.keys().public_key())
+    }
+}
+
+impl Drop for Harness {
+    fn drop(&mut self) {
+        self.handle.abort();
+    }
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn test_approve() {
+    let h = Harness::new();
+
+    assert!(h.signer.is_external_signer_installed().await.unwrap());
+    assert_eq!(
+        h.signer.get_public_key().await.unwrap(),
+        h.mock.keys().public_key()
+    );
+
+    let event: Event = h.signer.sign_event_as(h.unsigned()).await.unwrap();
+    assert_eq!(event.content, "hello");
+
+    let other: Keys = Keys::generate();
+    let ciphertext: String = h
+        .signer
+        .nip44_encrypt(&other.public_key(), "secret")
+        .await
+        .unwrap();
+    assert_eq!(
+        h.signer
+            .nip44_decrypt(&other.public_key(), &ciphertext)
+            .await
+            .unwrap(),
+        "secret"
+    );
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn test_filesystem_socket() {
+    let path: PathBuf = std::env::temp_dir().join(format!("{}.sock", unique_name()));
+    let keys: Keys = Keys::generate();
+    let mock = MockProxy::with_path(&path, keys.clone()).unwrap();
+    let handle = mock.spawn().unwrap();
+
+    let signer = AndroidSigner::with_path(&path, AndroidSignerOptions::new()).unwrap();
+    assert_eq!(signer.get_public_key().await.unwrap(), keys.public_key());
+
+    handle.abort();
+    let _ = std::fs::remove_file(&path);
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn test_rejected() {
+    let h = Harness::new();
+    let public_key: PublicKey = h.mock.keys().public_key();
+
+    h.mock
+        .set_behavior(Rpc::GetPublicKey, Behavior::reject())
+        .await;
+    assert!(matches!(h.signer.login().await, Err(Error::Rejected)));
+    assert!(h.signer.current_user_public_key().await.is_none());
+
+    h.mock
+        .set_behavior(Rpc::SignEvent, Behavior::reject())
+        .await;
+    assert!(matches!(
+        h.signer.sign_event_as(h.unsigned()).await,
+        Err(Error::Rejected)
+    ));
+
+    h.mock
+        .set_behavior(Rpc::Nip04Encrypt, Behavior::reject())
+        .await;
+    let res = h
+        .signer
+        .nip04_encrypt_as(&public_key, &Keys::generate().public_key(), "secret")
+        .await;
+    assert!(matches!(res, Err(Error::Rejected)));
+
+    // Approved again
+    h.mock.reset().await;
+    assert_eq!(h.signer.login().await.unwrap(), public_key);
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn test_failure_codes() {
+    let h = Harness::new();
+
+    let cases: [(Code, ErrorCheck); 8] = [
+        (Code::Unauthenticated, |e| {
+            matches!(e, Error::Unauthenticated)
+        }),
+        (Code::FailedPrecondition, |e| {
+            matches!(e, Error::SignerNotInstalled)
+        }),
+        (
+            Code::InvalidArgument,
+            |e| matches!(e, Error::InvalidArgument(msg) if msg == "failure"),
+        ),
+        (
+            Code::Unimplemented,
+            |e| matches!(e, Error::Unsupported(msg) if msg == "failure"),
+        ),
+        (Code::DeadlineExceeded, |e| matches!(e, Error::Timeout)),
+        (Code::Cancelled, |e| matches!(e, Error::Cancelled)),
+        (
+            Code::DataLoss,
+            |e| matches!(e, Error::InvalidSignerResponse(msg) if msg == "failure"),
+        ),
+        (
+            Code::Internal,
+            |e| matches!(e, Error::Status(status) if status.code() == Code::Internal),
+        ),
+    ];
+
+    for (code, check) in cases {
+        h.mock
+            .set_behavior(Rpc::SignEvent, Behavior::fail(code, "failure"))
+            .await;
+        let err: Error = h.signer.sign_event_as(h.unsigned()).await.unwrap_err();
+        assert!(check(&err), "{code:?}: unexpected error {err:?}");
+    }
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn test_malformed_replies() {
+    let h = Harness::new();
+
+    // Malformed or tampered replies mean not installed
+    h.mock
+        .set_behavior(Rpc::IsExternalSignerInstalled, Behavior::malformed())
+        .await;
+    assert!(!h.signer.is_external_signer_installed().await.unwrap());
+
+    h.mock
+        .set_behavior(Rpc::GetPublicKey, Behavior::malformed())
+        .await;
+    assert!(matches!(h.signer.login().await, Err(Error::Keys(..))));
+    assert!(h.signer.current_user_public_key().await.is_none());
+
+    h.mock
+        .set_behavior(Rpc::SignEvent, Behavior::malformed())
+        .await;
+    assert!(matches!(
+        h.signer.sign_event_as(h.unsigned()).await,
+        Err(Error::Event(..))
+    ));
+
+    h.mock
+        .set_behavior(Rpc::SignEvents, Behavior::malformed())
+        .await;
+    let results = h
+        .signer
+        .sign_events(vec![h.unsigned(), h.unsigned()])
+        .await
+        .unwrap();
+    assert_eq!(results.len(), 2);
+    assert!(results.iter().all(|r| matches!(r, Err(Error::Event(..)))));
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn test_tampered_replies() {
+    let h = Harness::new();
+
+    h.mock
+        .set_behavior(Rpc::SignEvent, Behavior::tampered())
+        .await;
+    assert!(matches!(
+        h.signer.sign_event_as(h.unsigned()).await,
+        Err(Error::SignedEventMismatch(..))
+    ));
+
+    h.mock
+        .set_behavior(Rpc::SignEvents, Behavior::tampered())
+        .await;
+    let results = h.signer.sign_events(vec![h.unsigned()]).await.unwrap();
+    assert!(matches!(
+        results.as_slice(),
+        [Err(Error::SignedEventMismatch(..))]
+    ));
+
+    // A different public key can't be detected, but it's the one used from now on
+    h.mock
+        .set_behavior(Rpc::GetPublicKey, Behavior::tampered())
+        .await;
+    let public_key: PublicKey = h.signer.login().await.unwrap();
+    assert_ne!(public_key, h.mock.keys().public_key());
+    assert_eq!(h.signer.current_user_public_key().await, Some(public_key));
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn test_signed_event_mismatch() {
+    let h = Harness::new();
+
+    let cases: [(EventField, &str); 5] = [
+        (EventField::PublicKey, "pubkey: expected"),
+        (EventField::CreatedAt, "created_at: expected"),
+        (EventField::Kind, "kind: expected"),
+        (EventField::Tags, "tags differ"),
+        (EventField::Content, "content differs"),
+    ];
+
+    for (field, expected) in cases {
+        h.mock
+            .set_behavior(Rpc::SignEvent, Behavior::tampered_event(field))
+            .await;
+        match h.signer.sign_event_as(h.unsigned()).await {
+            Err(Error::SignedEventMismatch(mismatch)) => {
+                // The id always differs
+                assert!(mismatch.starts_with("id: expected"), "{mismatch}");
+                assert!(mismatch.contains(expected), "{field:?}: {mismatch}");
+            }
+            res => panic!("{field:?}: unexpected result {res:?}"),
+        }
+
+        h.mock
+            .set_behavior(Rpc::SignEvents, Behavior::tampered_event(field))
+            .await;
+        let results = h.signer.sign_events(vec![h.unsigned()]).await.unwrap();
+        assert!(
+            matches!(results.as_slice(), [Err(Error::SignedEventMismatch(mismatch))] if mismatch.contains(expected)),
+            "{field:?}: unexpected results {results:?}"
+        );
+    }
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn test_sign_event_as_other_user() {
+    let h = Harness::new();
+    let other: UnsignedEvent =
+        EventBuilder::text_note("hello").build(Keys::generate().public_key());
+
+    // The signer interface only signs as the current user...
+    let res = NostrSigner::sign_event(&h.signer, other.clone()).await;
+    assert!(
+        res.unwrap_err()
+            .to_string()
+            .contains("not the current user")
+    );
+
+    // ...while other accounts of the signer app can be used explicitly
+    // (the mock only has one, so it refuses the request)
+    let res = h.signer.sign_event_as(other.clone()).await;
+    assert!(matches!(res, Err(Error::InvalidArgument(..))));
+    let results = h.signer.sign_events(vec![other]).await.unwrap();
+    assert!(matches!(
+        results.as_slice(),
+        [Err(Error::InvalidArgument(..))]
+    ));
+
+    let event: Event = NostrSigner::sign_event(&h.signer, h.unsigned())
+        .await
+        .unwrap();
+    assert_eq!(event.pubkey, h.mock.keys().public_key());
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn test_delays() {
+    let opts = AndroidSignerOptions::new()
+        .timeout(Duration::from_millis(200))
+        .interactive_timeout(Duration::from_secs(2));
+    let h = Harness::with_opts(opts);
+
+    // Interactive requests wait for the user
+    h.mock
+        .set_behavior(
+            Rpc::SignEvent,
+            Behavior::approve().delay(Duration::from_millis(500)),
+        )
+        .await;
+    h.signer.sign_event_as(h.unsigned()).await.unwrap();
+
+    // ...but not forever
+    h.mock
+        .set_behavior(
+            Rpc::SignEvent,
+            Behavior::approve().delay(Duration::from_secs(5)),
+        )
+        .await;
+    assert!(matches!(
+        h.signer.sign_event_as(h.unsigned()).await,
+        Err(Error::Timeout)
+    ));
+
+    // Non-interactive requests use the short timeout
+    h.mock
+        .set_behavior(
+            Rpc::IsExternalSignerInstalled,
+            Behavior::approve().delay(Duration::from_millis(500)),
+        )
+        .await;
+    assert!(matches!(
+        h.signer.is_external_signer_installed().await,
+        Err(Error::Timeout)
+    ));
+
+    // Delayed rejections are still rejections
+    h.mock
+        .set_behavior(
+            Rpc::SignEvent,
+            Behavior::reject().delay(Duration::from_millis(100)),
+        )
+        .await;
+    assert!(matches!(
+        h.signer.sign_event_as(h.unsigned()).await,
+        Err(Error::Rejected)
+    ));
+}
+
+/// Hook that records the prompts
+#[derive(Debug, Default)]
+struct RecordingHook {
+    approve: AtomicBool,
+    prompts: Mutex<Vec<(Rpc, String)>>,
+}
+
+impl ApprovalHook for RecordingHook {
+    fn approve(&self, rpc: Rpc, description: String) -> BoxedFuture<'_, bool> {
+        Box::pin(async move {
+            self.prompts.lock().unwrap().push((rpc, description));
+            self.approve.load(Ordering::SeqCst)
+        })
+    }
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn test_approval_hook() {
+    let name: String = unique_name();
+    let keys: Keys = Keys::generate();
+    let hook: Arc<RecordingHook> = Arc::new(RecordingHook::default());
+    let mock = MockProxy::new(&name, keys.clone())
+        .unwrap()
+        .hook(hook.clone());
+    let handle = mock.spawn().unwrap();
+    let signer = AndroidSigner::new(&name, AndroidSignerOptions::new()).unwrap();
+
+    // Not prompted
+    assert!(signer.is_external_signer_installed().await.unwrap());
+    assert!(hook.prompts.lock().unwrap().is_empty());
+
+    assert!(matches!(signer.login().await, Err(Error::Rejected)));
+
+    hook.approve.store(true, Ordering::SeqCst);
+    assert_eq!(signer.login().await.unwrap(), keys.public_key());
+    let unsigned: UnsignedEvent = EventBuilder::text_note("hello").build(keys.public_key());
+    signer.sign_event_as(unsigned.clone()).await.unwrap();
+
+    // A single prompt for a batch
+    let results = signer
+        .sign_events(vec![unsigned.clone(), unsigned])
+        .await
+        .unwrap();
+    assert!(results.iter().all(Result::is_ok));
+
+    let prompts = hook.prompts.lock().unwrap().clone();
+    assert_eq!(
+        prompts,
+        [
+            (Rpc::GetPublicKey, String::from("Share the public key")),
+            (Rpc::GetPublicKey, String::from("Share the public key")),
+            (Rpc::SignEvent, String::from("Sign event\n  kind 1: hello")),
+            (
+                Rpc::SignEvents,
+                String::from("Sign 2 events\n  kind 1: hello\n  kind 1: hello")
+            ),
+        ]
+    );
+
+    handle.abort();
+}
+
+#[test]
+fn test_policy_denied_status() {
+    // Denied by a policy rule
+    let mut status = Status::permission_denied("Denied");
+    status.metadata_mut().insert_bin(
+        POLICY_RULE_METADATA_KEY,
+        MetadataValue::from_bytes(b"no-nip04"),
+    );
+    match Error::from(status) {
+        Error::PolicyDenied(rule) => assert_eq!(rule, "no-nip04"),
+        e => panic!("Unexpected error: {e:?}"),
+    }
+
+    // Rejected by the user
+    let status = Status::permission_denied("Rejected");
+    assert!(matches!(Error::from(status), Error::Rejected));
+}
+
+/// Poll the condition until it's true, or panic after a few seconds
+async fn wait_until<F>(condition: F)
+where
+    F: Fn() -> bool,
+{
+    tokio::time::timeout(Duration::from_secs(5), async {
+        while !condition() {
+            tokio::time::sleep(Duration::from_millis(10)).await;
+        }
+    })
+    .await
+    .expect("condition not met");
+}
+
+/// Run the mock until the returned sender is used or dropped
+fn run_mock(mock: &MockProxy) -> (oneshot::Sender<()>, JoinHandle<()>) {
+    let (stop, stopped) = oneshot::channel::<()>();
+    let mock: MockProxy = mock.clone();
+    let handle = tokio::spawn(async move {
+        mock.run_until(async {
+            let _ = stopped.await;
+        })
+        .await
+        .unwrap();
+    });
+    (stop, handle)
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn test_reconnect() {
+    let name: String = unique_name();
+    let mock = MockProxy::new(&name, Keys::generate()).unwrap();
+    let opts = AndroidSignerOptions::new()
+        .min_reconnect_delay(Duration::from_millis(20))
+        .max_reconnect_delay(Duration::from_millis(100));
+    let signer = AndroidSigner::new(&name, opts).unwrap();
+    assert_eq!(signer.state(), ConnectionState::Disconnected);
+
+    // Record the state changes
+    let states: Arc<Mutex<Vec<ConnectionState>>> = Arc::new(Mutex::new(Vec::new()));
+    let mut watcher = signer.watch_state();
+    let recorder = tokio::spawn({
+        let states = states.clone();
+        async move {
+            while watcher.changed().await.is_ok() {
+                let state: ConnectionState = *watcher.borrow_and_update();
+                states.lock().unwrap().push(state);
+            }
+        }
+    });
+
+    for _ in 0..2 {
+        // The client retries, with a backoff, until the proxy is up
+        let request = tokio::spawn({
+            let signer = signer.clone();
+            async move { signer.is_external_signer_installed().await }
+        });
+        wait_until(|| signer.state() == ConnectionState::Connecting).await;
+        tokio::time::sleep(Duration::from_millis(200)).await;
+        assert_eq!(signer.state(), ConnectionState::Connecting);
+
+        let (stop, handle) = run_mock(&mock);
+        assert!(request.await.unwrap().unwrap());
+        assert_eq!(signer.state(), ConnectionState::Ready);
+
+        // The proxy goes away: the broken transport is detected
+        stop.send(()).unwrap();
+        handle.await.unwrap();
+        let res = signer.is_external_signer_installed().await;
+        assert!(res.is_err(), "{res:?}");
+        assert_eq!(signer.state(), ConnectionState::Disconnected);
+    }
+
+    drop(signer);
+    recorder.await.unwrap();
+    assert_eq!(
+        *states.lock().unwrap(),
+        [
+            ConnectionState::Connecting,
+            ConnectionState::Ready,
+            ConnectionState::Disconnected,
+            ConnectionState::Connecting,
+            ConnectionState::Ready,
+            ConnectionState::Disconnected,
+        ]
+    );
+}